@@ -0,0 +1,76 @@
+use crate::codes;
+use crate::types::{Type, ValueError};
+
+/// Builtins that are values like any other function, handled by `call`
+pub const FUNCTIONS: &[&str] = &["ord", "chr", "str", "len"];
+
+/// Keywords written like calls (`print(x)`, `range(n)`) that are not values; still suggested for misspelled names
+pub const KEYWORDS: &[&str] = &["print", "range"];
+
+/// The builtin function called `name`, for a name the script has not defined itself
+pub fn lookup(name: &str) -> Option<Type> {
+    FUNCTIONS.iter().find(|f| **f == name).map(|f| Type::Builtin(f))
+}
+
+/// Call the builtin `name`, or return `None` if there is no such builtin
+pub fn call(name: &str, args: Vec<Type>) -> Option<Result<Type, ValueError>> {
+    let result = match name {
+        "ord" => one_arg(name, args).and_then(ord),
+        "chr" => one_arg(name, args).and_then(chr),
+        "str" => one_arg(name, args).map(|v| Type::Str(v.to_string())),
+        "len" => one_arg(name, args).and_then(len),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn one_arg(name: &str, mut args: Vec<Type>) -> Result<Type, ValueError> {
+    if args.len() != 1 {
+        return Err(ValueError::new(
+            codes::WRONG_ARGUMENT_COUNT,
+            format!("Function '{}' expected 1 arguments, got {}", name, args.len()),
+        ));
+    }
+    Ok(args.remove(0))
+}
+
+// ord(c): the code point of a character
+fn ord(value: Type) -> Result<Type, ValueError> {
+    match value {
+        Type::Char(c) => Ok(Type::Int(c as i64)),
+        other => Err(ValueError::new(
+            codes::INVALID_ARGUMENT,
+            format!("ord() expects a Char, got {}", other.type_name()),
+        )),
+    }
+}
+
+// chr(n): the character for a code point, or the only character of a one-character string
+fn chr(value: Type) -> Result<Type, ValueError> {
+    match value {
+        Type::Int(n) => u32::try_from(n)
+            .ok()
+            .and_then(char::from_u32)
+            .map(Type::Char)
+            .ok_or_else(|| ValueError::new(codes::INVALID_ARGUMENT, format!("chr() got {}, which is not a valid code point", n))),
+        Type::Str(s) if s.chars().count() == 1 => Ok(Type::Char(s.chars().next().unwrap())),
+        Type::Char(c) => Ok(Type::Char(c)),
+        other => Err(ValueError::new(
+            codes::INVALID_ARGUMENT,
+            format!("chr() expects an Int code point or a one-character Str, got {}", other.type_name()),
+        )),
+    }
+}
+
+// len(x): the number of items in a list or map, or characters in a string
+fn len(value: Type) -> Result<Type, ValueError> {
+    match value {
+        Type::List(items) => Ok(Type::Int(items.borrow().len() as i64)),
+        Type::Map(map) => Ok(Type::Int(map.borrow().len() as i64)),
+        Type::Str(s) => Ok(Type::Int(s.chars().count() as i64)),
+        other => Err(ValueError::new(
+            codes::INVALID_ARGUMENT,
+            format!("len() expects a List, Map or Str, got {}", other.type_name()),
+        )),
+    }
+}
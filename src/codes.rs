@@ -0,0 +1,314 @@
+//! Stable codes for every diagnostic Fig can report, and the long-form
+//! explanations printed by `fig explain <code>`.
+//!
+//! Codes are never renumbered or reused: F00xx are lexer and parser errors,
+//! F01xx are runtime errors.
+
+// Syntax errors
+pub const EXPECTED_SEMICOLON: &str = "F0001";
+pub const EXPECTED_TOKEN: &str = "F0002";
+pub const EXPECTED_EXPRESSION: &str = "F0003";
+pub const EXPECTED_IDENTIFIER: &str = "F0004";
+pub const UNEXPECTED_CHARACTER: &str = "F0005";
+pub const UNTERMINATED_STRING: &str = "F0006";
+pub const INVALID_NUMBER: &str = "F0007";
+pub const INVALID_ESCAPE: &str = "F0008";
+pub const UNTERMINATED_INTERPOLATION: &str = "F0009";
+pub const INVALID_CHAR: &str = "F0010";
+pub const NUMBER_OUT_OF_RANGE: &str = "F0011";
+pub const BREAK_OUTSIDE_LOOP: &str = "F0012";
+pub const UNDEFINED_LABEL: &str = "F0013";
+pub const INVALID_ASSIGNMENT_TARGET: &str = "F0014";
+pub const ASSIGN_TO_CONSTANT: &str = "F0015"; // also raised at runtime when the parser could not tell
+pub const METHOD_WITHOUT_SELF: &str = "F0016";
+pub const SUPER_OUTSIDE_SUBCLASS: &str = "F0017";
+pub const SUPER_OUTSIDE_METHOD: &str = "F0018";
+
+// Runtime errors
+pub const INVALID_OPERANDS: &str = "F0101";
+pub const UNDEFINED_VARIABLE: &str = "F0102";
+pub const UNDEFINED_FUNCTION: &str = "F0103";
+pub const WRONG_ARGUMENT_COUNT: &str = "F0104";
+pub const DIVISION_BY_ZERO: &str = "F0105";
+pub const INTEGER_OVERFLOW: &str = "F0106";
+pub const NON_BOOLEAN_CONDITION: &str = "F0107";
+pub const INVALID_RANGE: &str = "F0108";
+pub const NOT_ITERABLE: &str = "F0109";
+pub const INVALID_ARGUMENT: &str = "F0110";
+pub const SHIFT_OUT_OF_RANGE: &str = "F0111";
+pub const INDEX_OUT_OF_BOUNDS: &str = "F0112";
+pub const UNHASHABLE_KEY: &str = "F0113";
+pub const KEY_NOT_FOUND: &str = "F0114";
+pub const UNDEFINED_FIELD: &str = "F0115";
+pub const NOT_A_CLASS: &str = "F0116";
+pub const NOT_CALLABLE: &str = "F0117";
+pub const RECURSION_LIMIT: &str = "F0118";
+
+/// Long-form description of a diagnostic code
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub wrong: &'static str,
+    pub fixed: &'static str,
+}
+
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: EXPECTED_SEMICOLON,
+        title: "expected ';'",
+        description: "Every statement in Fig ends with a semicolon, except blocks such as `if`, `for` and `fn` bodies.",
+        wrong: "var x = 1\nprint(x);",
+        fixed: "var x = 1;\nprint(x);",
+    },
+    Explanation {
+        code: EXPECTED_TOKEN,
+        title: "expected a different token",
+        description: "The parser needed a specific piece of punctuation or keyword here, such as a closing parenthesis or the `in` of a `for` loop.",
+        wrong: "for i range(3) {\n    print(i);\n}",
+        fixed: "for i in range(3) {\n    print(i);\n}",
+    },
+    Explanation {
+        code: EXPECTED_EXPRESSION,
+        title: "expected an expression",
+        description: "A value was needed here, such as a number, a string, a variable or a function call.",
+        wrong: "var x = ;",
+        fixed: "var x = 0;",
+    },
+    Explanation {
+        code: EXPECTED_IDENTIFIER,
+        title: "expected an identifier",
+        description: "Declarations need a name: `var` and `for` need a variable name, `fn` needs a function name and parameter names.",
+        wrong: "var = 5;",
+        fixed: "var count = 5;",
+    },
+    Explanation {
+        code: UNEXPECTED_CHARACTER,
+        title: "unexpected character",
+        description: "The character is not part of any Fig token. Check for stray punctuation or characters pasted from another language.",
+        wrong: "var total = 5 @ 2;",
+        fixed: "var total = 5 * 2;",
+    },
+    Explanation {
+        code: UNTERMINATED_STRING,
+        title: "unterminated string literal",
+        description: "A string was opened with `\"` but the file ended before the closing quote.",
+        wrong: "print(\"hello);",
+        fixed: "print(\"hello\");",
+    },
+    Explanation {
+        code: INVALID_NUMBER,
+        title: "invalid number literal",
+        description: "The number is malformed, for example it runs straight into letters, has a digit its base does not allow, or a `_` separator that is not between two digits. Numbers can be written as 42, 1_000_000, 0xFF, 0b1010, 0o17, 1.5, .5 and 1.5e-3.",
+        wrong: "var mask = 0b102;",
+        fixed: "var mask = 0b101;",
+    },
+    Explanation {
+        code: INVALID_ESCAPE,
+        title: "invalid escape sequence",
+        description: "Inside a string, a backslash starts an escape: \\n \\t \\r \\0 \\\\ \\\" \\', \\xNN for an ASCII code up to 7F, or \\u{NNNN} for any Unicode scalar value. Use a raw string (r\"...\") to keep backslashes as written.",
+        wrong: "var path = \"C:\\data\\new\";",
+        fixed: "var path = r\"C:\\data\\new\";",
+    },
+    Explanation {
+        code: UNTERMINATED_INTERPOLATION,
+        title: "unterminated interpolation",
+        description: "`${` inside a string starts an embedded expression that must be closed with `}`. To write a literal `${`, escape the dollar sign as `\\$`.",
+        wrong: "var price = 4;\nvar count = 3;\nprint(\"total: ${price * count\");",
+        fixed: "var price = 4;\nvar count = 3;\nprint(\"total: ${price * count}\");",
+    },
+    Explanation {
+        code: INVALID_CHAR,
+        title: "invalid character literal",
+        description: "A character literal holds exactly one character (or one escape) between single quotes. Use double quotes for text of any other length.",
+        wrong: "var grade = 'ab';",
+        fixed: "var grade = 'a';\nvar initials = \"ab\";",
+    },
+    Explanation {
+        code: NUMBER_OUT_OF_RANGE,
+        title: "number literal out of range",
+        description: "Int literals must fit in a 64-bit signed integer and Float literals must be finite 64-bit floats. A leading `-` is an operator applied to the literal, so the literal itself can be at most 9223372036854775807; the smallest Int is written `-9223372036854775807 - 1`.",
+        wrong: "var big = 99999999999999999999;",
+        fixed: "var big = 99999999999999999999.0;",
+    },
+    Explanation {
+        code: BREAK_OUTSIDE_LOOP,
+        title: "`break` or `continue` outside of a loop",
+        description: "`break` and `continue` only work inside the body of a `for` or `while` loop. A function body starts fresh, so they cannot reach a loop that encloses the function; use `return` there instead.",
+        wrong: "fn first(n) {\n    break;\n}",
+        fixed: "fn first(n) {\n    return n;\n}",
+    },
+    Explanation {
+        code: UNDEFINED_LABEL,
+        title: "undefined loop label",
+        description: "`break` and `continue` can name the loop they apply to, but the label must belong to a loop that encloses the statement. Labels are written before the loop as `name: for ...` or `name: while ...`.",
+        wrong: "for i in range(3) {\n    for j in range(3) {\n        break outer;\n    }\n}",
+        fixed: "outer: for i in range(3) {\n    for j in range(3) {\n        break outer;\n    }\n}",
+    },
+    Explanation {
+        code: INVALID_ASSIGNMENT_TARGET,
+        title: "invalid assignment target",
+        description: "The left-hand side of `=` must be a variable. Use `==` to compare two values.",
+        wrong: "var x = 2;\nif (x + 1 = 3) {\n    print(x);\n}",
+        fixed: "var x = 2;\nif (x + 1 == 3) {\n    print(x);\n}",
+    },
+    Explanation {
+        code: ASSIGN_TO_CONSTANT,
+        title: "assignment to a constant",
+        description: "A binding declared with `const` cannot be changed after it is declared, by `=`, by compound assignment such as `+=`, or by declaring the same name again with `var`, `const`, `fn` or `class` in the same scope. Declare it with `var` if it needs to change, or shadow it with a new `var` in an inner scope.",
+        wrong: "const limit = 10;\nlimit += 5;",
+        fixed: "var limit = 10;\nlimit += 5;",
+    },
+    Explanation {
+        code: METHOD_WITHOUT_SELF,
+        title: "method without `self`",
+        description: "Every method receives the object it was called on as its first parameter, which must be named `self`. Fields are reached through it, as `self.name`.",
+        wrong: "class Counter() {\n    count = 0;\n    fn bump() {\n        count += 1;\n    }\n}",
+        fixed: "class Counter() {\n    count = 0;\n    fn bump(self) {\n        self.count += 1;\n    }\n}",
+    },
+    Explanation {
+        code: SUPER_OUTSIDE_SUBCLASS,
+        title: "`super` outside a subclass",
+        description: "`super.method(...)` calls the parent class's version of a method on `self`, so it can only be used inside the methods of a class that inherits from another, as in `class Manager(Employee)`.",
+        wrong: "class Employee() {\n    fn describe(self) {\n        return super.describe();\n    }\n}",
+        fixed: "class Person() {\n    fn describe(self) {\n        return \"person\";\n    }\n}\nclass Employee(Person) {\n    fn describe(self) {\n        return super.describe() + \", employee\";\n    }\n}",
+    },
+    Explanation {
+        code: SUPER_OUTSIDE_METHOD,
+        title: "`super` outside a method",
+        description: "A field default is evaluated while the object is being created, before there is a `self` for `super` to act on. Call `super` from a method such as `init` instead.",
+        wrong: "class Person() {\n    fn label(self) {\n        return \"person\";\n    }\n}\nclass Employee(Person) {\n    title = super.label();\n}",
+        fixed: "class Person() {\n    fn label(self) {\n        return \"person\";\n    }\n}\nclass Employee(Person) {\n    title;\n    fn init(self) {\n        self.title = super.label();\n    }\n}\nprint(Employee().title);",
+    },
+    Explanation {
+        code: INVALID_OPERANDS,
+        title: "invalid operand types",
+        description: "The operator is not defined for these types of values, for example subtracting a string from a number.",
+        wrong: "var x = 10 - \"3\";",
+        fixed: "var x = 10 - 3;",
+    },
+    Explanation {
+        code: UNDEFINED_VARIABLE,
+        title: "undefined variable",
+        description: "The variable is not declared in the current scope or any enclosing scope. Variables declared inside a block are not visible outside it, and assigning with `name = value` only changes an existing variable; declare new ones with `var`.",
+        wrong: "if (true) {\n    var x = 1;\n}\nprint(x);",
+        fixed: "var x = 0;\nif (true) {\n    x = 1;\n}\nprint(x);",
+    },
+    Explanation {
+        code: UNDEFINED_FUNCTION,
+        title: "undefined function",
+        description: "No function with this name has been defined. Functions must be defined before the statement that calls them runs.",
+        wrong: "print(add(1, 2));\nfn add(a, b) {\n    return a + b;\n}",
+        fixed: "fn add(a, b) {\n    return a + b;\n}\nprint(add(1, 2));",
+    },
+    Explanation {
+        code: WRONG_ARGUMENT_COUNT,
+        title: "wrong number of arguments",
+        description: "A function was called with a different number of arguments than it has parameters.",
+        wrong: "fn add(a, b) {\n    return a + b;\n}\nprint(add(1));",
+        fixed: "fn add(a, b) {\n    return a + b;\n}\nprint(add(1, 2));",
+    },
+    Explanation {
+        code: DIVISION_BY_ZERO,
+        title: "division by zero",
+        description: "The right-hand side of `/`, `//` or `%` was zero. Check the divisor before dividing.",
+        wrong: "var total = 10;\nvar count = 0;\nvar avg = total / count;",
+        fixed: "var total = 10;\nvar count = 0;\nif (count != 0) {\n    var avg = total / count;\n}",
+    },
+    Explanation {
+        code: INTEGER_OVERFLOW,
+        title: "integer overflow",
+        description: "The result of integer arithmetic does not fit in a 64-bit signed integer. Use floats for very large values.",
+        wrong: "var big = 9223372036854775807 + 1;",
+        fixed: "var big = 9223372036854775807.0 + 1;",
+    },
+    Explanation {
+        code: NON_BOOLEAN_CONDITION,
+        title: "condition is not a boolean",
+        description: "`if`, `elif` and `while` conditions must evaluate to `true` or `false`; Fig does not treat numbers or strings as truthy.",
+        wrong: "var count = 3;\nif (count) {\n    print(count);\n}",
+        fixed: "var count = 3;\nif (count > 0) {\n    print(count);\n}",
+    },
+    Explanation {
+        code: INVALID_RANGE,
+        title: "invalid range argument",
+        description: "`range(n)` needs a non-negative integer.",
+        wrong: "for i in range(2.5) {\n    print(i);\n}",
+        fixed: "for i in range(3) {\n    print(i);\n}",
+    },
+    Explanation {
+        code: NOT_ITERABLE,
+        title: "value is not iterable",
+        description: "A `for` loop can only iterate over a value that produces items: `range(n)`, a list, a map, which yields its keys in insertion order, or a string, which yields its characters.",
+        wrong: "for i in 10 {\n    print(i);\n}",
+        fixed: "for i in range(10) {\n    print(i);\n}",
+    },
+    Explanation {
+        code: INVALID_ARGUMENT,
+        title: "invalid argument to a builtin",
+        description: "A builtin function was given a value it cannot work with, such as `ord` with something other than a character or `chr` with a number that is not a Unicode code point.",
+        wrong: "print(ord(\"ab\"));",
+        fixed: "print(ord('a'));",
+    },
+    Explanation {
+        code: SHIFT_OUT_OF_RANGE,
+        title: "shift amount out of range",
+        description: "`<<` and `>>` shift a 64-bit Int, so the number of bits to shift by must be between 0 and 63.",
+        wrong: "var mask = 1 << 64;",
+        fixed: "var mask = 1 << 63;",
+    },
+    Explanation {
+        code: INDEX_OUT_OF_BOUNDS,
+        title: "index out of bounds",
+        description: "A list or string was indexed past its end. Valid indices run from 0 to length - 1, or from -length to -1 counting back from the end. Slices such as `xs[1:10]` never fail; they stop at the end.",
+        wrong: "var xs = [1, 2, 3];\nprint(xs[3]);",
+        fixed: "var xs = [1, 2, 3];\nprint(xs[-1]);",
+    },
+    Explanation {
+        code: UNHASHABLE_KEY,
+        title: "unhashable map key",
+        description: "Map keys must be Int, Str, Bool or Char values. Floats are not exact enough to look up reliably, and Lists and Maps can change after they are used as a key.",
+        wrong: "var prices = {1.5: \"small\"};",
+        fixed: "var prices = {\"1.5\": \"small\"};",
+    },
+    Explanation {
+        code: KEY_NOT_FOUND,
+        title: "key not found in map",
+        description: "`m[key]` reads a key that the map does not have. Check for it first with `key in m`.",
+        wrong: "var ages = {\"ann\": 31};\nprint(ages[\"bob\"]);",
+        fixed: "var ages = {\"ann\": 31};\nif (\"bob\" in ages) {\n    print(ages[\"bob\"]);\n}",
+    },
+    Explanation {
+        code: UNDEFINED_FIELD,
+        title: "undefined field or method",
+        description: "The object's class does not declare a field or method with this name. Fields must be listed in the class body before they can be read or assigned.",
+        wrong: "class Point() {\n    x;\n}\nvar p = Point();\np.y = 2;",
+        fixed: "class Point() {\n    x;\n    y;\n}\nvar p = Point();\np.y = 2;",
+    },
+    Explanation {
+        code: NOT_A_CLASS,
+        title: "inheriting from something that is not a class",
+        description: "The name in parentheses after a class name must be a class declared earlier. Its fields and methods are inherited.",
+        wrong: "var Base = 1;\nclass Derived(Base) {}",
+        fixed: "class Base() {}\nclass Derived(Base) {}",
+    },
+    Explanation {
+        code: NOT_CALLABLE,
+        title: "call of a value that is not a function",
+        description: "Only functions, lambdas, methods and classes can be called with `(...)`. Check that the name refers to a function and not to a variable holding some other value.",
+        wrong: "var total = 10;\nprint(total(2));",
+        fixed: "fn total(n) {\n    return n * 10;\n}\nprint(total(2));",
+    },
+    Explanation {
+        code: RECURSION_LIMIT,
+        title: "maximum recursion depth exceeded",
+        description: "Too many function calls were active at once, usually because a recursive function never reaches the case that stops it. The limit is 1000 nested calls.",
+        wrong: "fn countdown(n) {\n    print(n);\n    return countdown(n - 1);\n}\ncountdown(3);",
+        fixed: "fn countdown(n) {\n    if (n < 0) {\n        return;\n    }\n    print(n);\n    return countdown(n - 1);\n}\ncountdown(3);",
+    },
+];
+
+/// Look up the explanation for a code such as `F0001` (case-insensitive)
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS.iter().find(|e| e.code.eq_ignore_ascii_case(code))
+}
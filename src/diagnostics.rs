@@ -0,0 +1,259 @@
+use crate::token::Span;
+use std::io::IsTerminal;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+}
+
+/// A problem found in a Fig program, reported the same way whether it came
+/// from the lexer, the parser or the evaluator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str, // stable code from `codes`, e.g. "F0001"
+    pub message: String,
+    pub span: Span,
+    pub label: Option<String>,    // short text printed under the caret
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub trace: Vec<(String, Span)>, // active Fig calls, innermost first
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span,
+            label: None,
+            notes: Vec::new(),
+            help: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Render the diagnostic rustc-style: a header, the offending source line with the span
+    /// underlined, then any notes, help and call trace.
+    pub fn render(&self, source: &str, colour: bool) -> String {
+        let paint = |code: &str, text: &str| {
+            if colour {
+                format!("\x1b[{code}m{text}\x1b[0m")
+            } else {
+                text.to_string()
+            }
+        };
+        let (severity, severity_colour) = match self.severity {
+            Severity::Error => ("error", "1;31"),
+        };
+
+        let line_no = self.span.line.to_string();
+        let gutter = " ".repeat(line_no.len());
+        let bar = paint("1;34", "|");
+
+        let mut out = format!(
+            "{}{}\n",
+            paint(severity_colour, &format!("{}[{}]", severity, self.code)),
+            paint("1", &format!(": {}", self.message))
+        );
+        out.push_str(&format!("{}{} {}\n", gutter, paint("1;34", "-->"), self.span));
+
+        if let Some(line) = source.lines().nth(self.span.line.saturating_sub(1)) {
+            // underline from the span's column to its end, clipped to this line
+            let line_start = source[..self.span.start.min(source.len())]
+                .rfind('\n')
+                .map_or(0, |i| i + 1);
+            let line_end = line_start + line.len();
+            let end = self.span.end.clamp(self.span.start, line_end);
+            let width = source
+                .get(self.span.start..end)
+                .map_or(0, |s| s.chars().count())
+                .max(1);
+            let padding = " ".repeat(self.span.column.saturating_sub(1));
+            let mut underline = "^".repeat(width);
+            if let Some(label) = &self.label {
+                underline = format!("{underline} {label}");
+            }
+
+            out.push_str(&format!("{} {}\n", gutter, bar));
+            out.push_str(&format!("{} {} {}\n", paint("1;34", &line_no), bar, line));
+            out.push_str(&format!("{} {} {}{}\n", gutter, bar, padding, paint(severity_colour, &underline)));
+        }
+
+        if !self.notes.is_empty() || !self.help.is_empty() || !self.trace.is_empty() {
+            out.push_str(&format!("{} {}\n", gutter, bar));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("{} {} {}\n", gutter, paint("1;34", "="), paint("1", &format!("note: {note}"))));
+        }
+        for help in &self.help {
+            out.push_str(&format!("{} {} {}\n", gutter, paint("1;34", "="), paint("1", &format!("help: {help}"))));
+        }
+        for (i, (function, span)) in self.trace.iter().enumerate() {
+            let prefix = if i == 0 { "trace:" } else { "      " };
+            out.push_str(&format!("{} {} {} at {} ({})\n", gutter, paint("1;34", if i == 0 { "=" } else { " " }), paint("1", prefix), function, span));
+        }
+        out.push('\n');
+        out
+    }
+
+    /// Serialize as a single-line JSON object.
+    /// Help lines, notes and trace frames all go in `notes`, each tagged with its `kind`.
+    pub fn to_json(&self, source: &str) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+        };
+        let label = self.label.as_deref().map_or("null".to_string(), json_string);
+
+        let mut notes = Vec::new();
+        for note in &self.notes {
+            notes.push(format!("{{\"kind\":\"note\",\"message\":{}}}", json_string(note)));
+        }
+        for help in &self.help {
+            notes.push(format!("{{\"kind\":\"help\",\"message\":{}}}", json_string(help)));
+        }
+        for (function, span) in &self.trace {
+            notes.push(format!(
+                "{{\"kind\":\"trace\",\"message\":{},{}}}",
+                json_string(&format!("at {}", function)),
+                json_location(span, source)
+            ));
+        }
+
+        format!(
+            "{{\"severity\":\"{}\",\"code\":\"{}\",\"message\":{},{},\"label\":{},\"notes\":[{}]}}",
+            severity,
+            self.code,
+            json_string(&self.message),
+            json_location(&self.span, source),
+            label,
+            notes.join(",")
+        )
+    }
+}
+
+/// Escape `text` as a JSON string literal
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// 1-based line and column of a byte offset in `source`
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+/// JSON object describing where `span` is
+fn json_location(span: &Span, source: &str) -> String {
+    let (end_line, end_column) = line_column(source, span.end);
+    format!(
+        "\"file\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{},\"byte_start\":{},\"byte_end\":{}",
+        json_string(&span.file),
+        span.line,
+        span.column,
+        end_line,
+        end_column,
+        span.start,
+        span.end
+    )
+}
+
+/// Edit distance between two names, counted in characters.
+/// Swapping two adjacent characters counts as one edit, since that is the most common typo.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Pick the candidate closest to `name`, if any is close enough to plausibly be a typo
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|c| *c != name)
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// How `fig` prints diagnostics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Human, // rendered with source snippets
+    Json,  // one JSON object per line, for editors and CI
+}
+
+/// Whether diagnostics written to stderr should use ANSI colour
+pub fn use_colour() -> bool {
+    std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+}
+
+/// Print an error that has no place in the source, such as a file that cannot be read.
+/// In JSON it has the same keys as a diagnostic, with `null` code and location apart from `file`.
+pub fn emit_failure(message: &str, file: &str, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => eprintln!("error: {message}"),
+        ErrorFormat::Json => eprintln!(
+            "{{\"severity\":\"error\",\"code\":null,\"message\":{},\"file\":{},\"line\":null,\"column\":null,\"end_line\":null,\"end_column\":null,\"byte_start\":null,\"byte_end\":null,\"label\":null,\"notes\":[]}}",
+            json_string(message),
+            json_string(file)
+        ),
+    }
+}
+
+/// Print a diagnostic to stderr
+pub fn emit(diagnostic: &Diagnostic, source: &str, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => eprint!("{}", diagnostic.render(source, use_colour())),
+        ErrorFormat::Json => eprintln!("{}", diagnostic.to_json(source)),
+    }
+}
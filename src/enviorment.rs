@@ -1,21 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::types::Type;
-use crate::helper::Stmt;
+use crate::helper::{Field, Stmt};
 use std::rc::Rc;
 use std::cell::RefCell;
 /// A simple runtime environment mapping variable names to Types.
 pub struct Environment {
     values: HashMap<String, Type>,
+    constants: HashSet<String>, // names in `values` declared with `const`
     parent: Option<Rc<RefCell<Environment>>>,
-    functions: HashMap<String, Function>,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment { 
             values: HashMap::new(),
+            constants: HashSet::new(),
             parent: None,
-            functions: HashMap::new(),
          }
     }
 
@@ -23,69 +23,189 @@ impl Environment {
     pub fn new_scope(parent: Rc<RefCell<Environment>>) -> Self {
         Environment {
             values: HashMap::new(),
+            constants: HashSet::new(),
             parent: Some(parent),
-            functions: HashMap::new(),
         }
     }
 
-    /// Define or reassign a variable
-    pub fn define(&mut self, name: String, value: Type) {
+    /// Define a variable in this scope, replacing one of the same name declared here unless that is a constant
+    pub fn define(&mut self, name: String, value: Type) -> Result<(), UpdateError> {
+        if self.constants.contains(&name) {
+            return Err(UpdateError::Constant);
+        }
         self.values.insert(name, value);
+        Ok(())
+    }
+
+    /// Define a variable that `update` will refuse to change
+    pub fn define_const(&mut self, name: String, value: Type) -> Result<(), UpdateError> {
+        self.define(name.clone(), value)?;
+        self.constants.insert(name);
+        Ok(())
     }
 
     /// Look up a variable; search current scope, then parents
-    pub fn get(&self, name: &str) -> Type {
+    pub fn get(&self, name: &str) -> Option<Type> {
         if let Some(val) = self.values.get(name) {
-            return val.clone();
+            return Some(val.clone());
         }
-        if let Some(ref parent) = self.parent {
-            return parent.borrow().get(name);
-        }
-        panic!("Undefined variable: {}", name);
+        self.parent.as_ref().and_then(|parent| parent.borrow().get(name))
     }
 
-    /// Check if a variable is defined in the current or parent scopes
-    pub fn is_defined(&self, name: &str) -> bool {
-        if self.values.contains_key(name) {
-            return true;
+    /// Update a variable in the closest scope it’s defined in
+    pub fn update(&mut self, name: String, value: Type) -> Result<(), UpdateError> {
+        if let Some(slot) = self.values.get_mut(&name) {
+            if self.constants.contains(&name) {
+                return Err(UpdateError::Constant);
+            }
+            *slot = value;
+            return Ok(());
         }
         if let Some(ref parent) = self.parent {
-            return parent.borrow().is_defined(name);
+            return parent.borrow_mut().update(name, value);
         }
-        false
+        Err(UpdateError::Undefined)
     }
 
-    /// Update a variable in the closest scope it’s defined in
-    pub fn update(&mut self, name: String, value: Type) {
-        if self.values.contains_key(&name) {
-            self.values.insert(name, value);
-            return;
-        }
+    /// Names of every variable visible from this scope, functions and classes included
+    pub fn visible_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().cloned().collect();
         if let Some(ref parent) = self.parent {
-            parent.borrow_mut().update(name, value);
-            return;
+            names.extend(parent.borrow().visible_names());
+        }
+        names
+    }
+}
+
+/// Why `Environment::update` or `define` could not change a variable
+#[derive(Debug, PartialEq)]
+pub enum UpdateError {
+    Undefined,
+    Constant,
+}
+
+/// A function value: declared with `fn name(...)`, written as a lambda, or a method bound to its object
+#[derive(Clone)]
+pub struct Function {
+    pub name: String, // shown in call traces; `<lambda>` for anonymous functions
+    pub params: Vec<String>,
+    pub body: Rc<Stmt>,
+    pub closure: Rc<RefCell<Environment>>,
+}
+
+impl Function {
+    /// This method with `object` as `self`, taking the rest of its parameters
+    pub fn bind(&self, object: Type) -> Function {
+        let mut scope = Environment::new_scope(Rc::clone(&self.closure));
+        scope.define("self".to_string(), object).expect("a new scope has no constants");
+        Function {
+            name: self.name.clone(),
+            params: self.params[1..].to_vec(),
+            body: Rc::clone(&self.body),
+            closure: Rc::new(RefCell::new(scope)),
         }
-        panic!("Undefined variable: {}", name);
     }
+}
+
+/// A class declared with `class`
+pub struct Class {
+    pub name: String,
+    pub parent: Option<Rc<Class>>,
+    pub fields: Vec<Field>, // declared here, in order, with optional default values
+    pub methods: HashMap<String, Rc<Function>>, // declared here; `find_method` also searches the parents
+    pub closure: Rc<RefCell<Environment>>, // where field defaults are evaluated
+    resolved: RefCell<HashMap<String, Option<Rc<Function>>>>, // results of `find_method`
+}
 
-    pub fn define_function(&mut self, name: String, function: Function) {
-        self.functions.insert(name, function);
+impl Class {
+    pub fn new(
+        name: String,
+        parent: Option<Rc<Class>>,
+        fields: Vec<Field>,
+        methods: HashMap<String, Rc<Function>>,
+        closure: Rc<RefCell<Environment>>,
+    ) -> Self {
+        Class { name, parent, fields, methods, closure, resolved: RefCell::new(HashMap::new()) }
     }
 
-    pub fn get_function(&self, name: &str) -> Option<Function> {
-        if let Some(func) = self.functions.get(name) {
-            return Some(func.clone());
+    /// Find a method declared here or in the closest parent that declares it.
+    /// A class cannot change once declared, so each name is only looked up through the chain once.
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        if let Some(found) = self.resolved.borrow().get(name) {
+            return found.clone();
         }
-        if let Some(ref parent) = self.parent {
-            return parent.borrow().get_function(name);
+        let found = match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.parent.as_ref().and_then(|parent| parent.find_method(name)),
+        };
+        self.resolved.borrow_mut().insert(name.to_string(), found.clone());
+        found
+    }
+
+    /// Names of every field an instance has, inherited ones first
+    pub fn field_names(&self) -> Vec<&str> {
+        let mut names = self.parent.as_ref().map_or_else(Vec::new, |parent| parent.field_names());
+        for (name, _) in &self.fields {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
         }
-        None
+        names
+    }
+
+    /// Names of every method an instance can call
+    pub fn method_names(&self) -> Vec<&str> {
+        let mut names = self.parent.as_ref().map_or_else(Vec::new, |parent| parent.method_names());
+        names.extend(self.methods.keys().map(String::as_str));
+        names
+    }
+
+    /// Whether this is `other` or inherits from it
+    pub fn is_subclass_of(&self, other: &Class) -> bool {
+        self == other || self.parent.as_ref().is_some_and(|parent| parent.is_subclass_of(other))
     }
 }
 
-#[derive(Clone)]
-pub struct Function {
-    pub params: Vec<String>,
-    pub body: Stmt,
-    pub closure: Rc<RefCell<Environment>>,
+/// An object made by calling a class. Values share the instance, so a change made
+/// through one of them is visible through all of them.
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: HashMap<String, Type>,
+}
+
+// Functions, classes and instances are compared by identity and printed by name
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialEq for Class {
+    fn eq(&self, other: &Class) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Instance) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Function({})", self.name)
+    }
+}
+
+impl std::fmt::Debug for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Class({})", self.name)
+    }
+}
+
+impl std::fmt::Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Instance({})", self.class.name)
+    }
 }
@@ -1,194 +1,772 @@
-use crate::token::Token;
-use crate::helper::{Expr, Stmt};
-use crate::types::Type;
-use crate::enviorment::{Environment, Function};
-use std::cell::RefCell;
+use crate::token::{Span, Token};
+use crate::helper::{Expr, ExprKind, Stmt, StmtKind};
+use crate::builtins;
+use crate::codes;
+use crate::types::{Key, Map, Type, ValueError};
+use crate::diagnostics::{did_you_mean, Diagnostic};
+use crate::enviorment::{Class, Environment, Function, Instance, UpdateError};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
 
 enum ExecResult {
     Value(Option<Type>),
     Return(Type),
+    Break(Option<String>),    // leave the loop with this label, or the innermost loop
+    Continue(Option<String>), // start the next iteration of that loop
+}
+
+/// Whether a `break` or `continue` aimed at `target` is meant for the loop labelled `label`
+fn targets(label: &Option<String>, target: &Option<String>) -> bool {
+    target.is_none() || target == label
+}
+
+/// An error raised while running a program.
+/// `span` is where it happened and `trace` holds the Fig calls that were active, innermost first.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub label: Option<String>,
+    pub help: Vec<String>,
+    pub trace: Vec<Frame>,
+}
+
+/// One active function call: the function's name and the call expression that entered it
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub function: String,
+    pub call_site: Span,
+}
+
+impl RuntimeError {
+    pub fn new(code: &'static str, message: impl Into<String>, span: &Span) -> Self {
+        RuntimeError {
+            code,
+            message: message.into(),
+            span: span.clone(),
+            label: None,
+            help: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Error for a name that is not defined, suggesting the closest visible name
+    fn undefined(code: &'static str, kind: &str, name: &str, span: &Span, env: &Environment) -> Self {
+        let names = env.visible_names();
+        let builtin_names = builtins::FUNCTIONS.iter().chain(builtins::KEYWORDS).copied();
+        let candidates = names.iter().map(String::as_str).chain(builtin_names);
+        let mut error = RuntimeError::new(code, format!("Undefined {}: {}", kind, name), span);
+        error.label = Some("not found in this scope".to_string());
+        match did_you_mean(name, candidates) {
+            Some(suggestion) => error.with_help(format!("did you mean `{}`?", suggestion)),
+            None => error,
+        }
+    }
+
+    /// Convert into a diagnostic whose trace lists, for each active call, where it had got to
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(self.code, self.message.clone(), self.span.clone());
+        diagnostic.label = self.label.clone();
+        diagnostic.help = self.help.clone();
+        if self.trace.is_empty() {
+            return diagnostic; // raised at the top level, a trace would only repeat the span
+        }
+        // each frame ran until the next inner frame was called (or the error was raised)
+        let mut location = &self.span;
+        let mut repeats = 0;
+        for frame in &self.trace {
+            // runaway recursion repeats one frame many times; show it once with a count
+            let entry = (frame.function.clone(), location.clone());
+            location = &frame.call_site;
+            if diagnostic.trace.last() == Some(&entry) {
+                repeats += 1;
+                continue;
+            }
+            push_repeats(&mut diagnostic.trace, repeats);
+            repeats = 0;
+            diagnostic.trace.push(entry);
+        }
+        push_repeats(&mut diagnostic.trace, repeats);
+        diagnostic.trace.push(("<script>".to_string(), location.clone()));
+        diagnostic
+    }
+}
+
+/// Note that the last trace frame was followed by `repeats` more identical ones
+fn push_repeats(trace: &mut Vec<(String, Span)>, repeats: usize) {
+    if repeats == 0 {
+        return;
+    }
+    let (function, span) = trace.last().cloned().expect("a repeated frame follows the one it repeats");
+    trace.push((format!("{} (same call repeated {} more times)", function, repeats), span));
 }
 
 /// Evaluate an expression in the given environment.
-fn eval_expr(expr: &Expr, env: &Rc<RefCell<Environment>>) -> Type {
-    match expr {
-        Expr::Number(n) => Type::Int(*n),
-        Expr::Float(f)  => Type::Float(*f),
-        Expr::String(s) => Type::Str(s.clone()),
-        Expr::Bool(b) => Type::Bool(*b),
-        Expr::Var(name) => env.borrow().get(name),
-        Expr::Binary { left, op, right } => {
-            let l = eval_expr(left, env);
-            let r = eval_expr(right, env);
-            match op {
-                Token::Plus  => Type::add(l, r),
-                Token::Minus => Type::subtract(l, r),
-                Token::Star  => Type::multiply(l, r),
-                Token::Slash => Type::divide(l, r),
-                Token::DblEqual => Type::equal(l, r),
-                Token::Range => {
-                    match l {
-                        Type::Int(n) if n >= 0 => Type::Range(n),
-                        Type::Int(_) => panic!("Range argument must be non-negative"),
-                        _ => panic!("Range argument must be an integer"),
-                    }
+fn eval_expr(expr: &Expr, env: &Rc<RefCell<Environment>>) -> Result<Type, RuntimeError> {
+    let value = match &expr.kind {
+        ExprKind::Number(n) => Type::Int(*n),
+        ExprKind::Float(f)  => Type::Float(*f),
+        ExprKind::String(s) => Type::Str(s.clone()),
+        ExprKind::Interpolation(parts) => {
+            let mut text = String::new();
+            for part in parts {
+                text.push_str(&eval_expr(part, env)?.to_string());
+            }
+            Type::Str(text)
+        }
+        ExprKind::Char(c) => Type::Char(*c),
+        ExprKind::Bool(b) => Type::Bool(*b),
+        ExprKind::Var(name) => {
+            lookup(name, env).ok_or_else(|| RuntimeError::undefined(codes::UNDEFINED_VARIABLE, "variable", name, &expr.span, &env.borrow()))?
+        }
+        ExprKind::Assign { name, op, value } => {
+            let undefined = || {
+                let error = RuntimeError::undefined(codes::UNDEFINED_VARIABLE, "variable", name, &expr.span, &env.borrow());
+                if error.help.is_empty() {
+                    error.with_help(format!("use `var {} = ...` to declare a new variable", name))
+                } else {
+                    error
+                }
+            };
+            let v = match op {
+                // `x += v` reads `x` once, then stores `x + v`
+                Some(op) => {
+                    let current = env.borrow().get(name).ok_or_else(undefined)?;
+                    let r = eval_expr(value, env)?;
+                    binary(op, current, r).map_err(|e| RuntimeError::new(e.code, e.message, &expr.span))?
+                }
+                None => eval_expr(value, env)?,
+            };
+            let updated = env.borrow_mut().update(name.clone(), v.clone());
+            match updated {
+                Ok(()) => v,
+                Err(UpdateError::Undefined) => return Err(undefined()),
+                Err(UpdateError::Constant) => {
+                    let mut error = RuntimeError::new(
+                        codes::ASSIGN_TO_CONSTANT,
+                        format!("Cannot assign to constant: {}", name),
+                        &expr.span,
+                    );
+                    error.label = Some("cannot assign twice to a constant".to_string());
+                    return Err(error.with_help(format!("declare it with `var {} = ...` if it needs to change", name)));
+                }
+            }
+        }
+        ExprKind::List(elements) => {
+            let items = elements
+                .iter()
+                .map(|e| eval_expr(e, env))
+                .collect::<Result<Vec<Type>, RuntimeError>>()?;
+            Type::list(items)
+        }
+        ExprKind::Map(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let k = eval_expr(key, env)?;
+                let k = Key::from_type(&k).map_err(|e| RuntimeError::new(e.code, e.message, &key.span))?;
+                map.insert(k, eval_expr(value, env)?);
+            }
+            Type::map(map)
+        }
+        ExprKind::Index { object, index } => {
+            let object = eval_expr(object, env)?;
+            let index = eval_expr(index, env)?;
+            object.index(index).map_err(|e| RuntimeError::new(e.code, e.message, &expr.span))?
+        }
+        ExprKind::Slice { object, start, end } => {
+            let object = eval_expr(object, env)?;
+            let start = start.as_ref().map(|e| eval_expr(e, env)).transpose()?;
+            let end = end.as_ref().map(|e| eval_expr(e, env)).transpose()?;
+            object.slice(start, end).map_err(|e| RuntimeError::new(e.code, e.message, &expr.span))?
+        }
+        ExprKind::SetIndex { object, index, op, value } => {
+            // the list and index are evaluated once, even for `xs[i] += v`
+            let object = eval_expr(object, env)?;
+            let index = eval_expr(index, env)?;
+            let to_runtime = |e: ValueError| RuntimeError::new(e.code, e.message, &expr.span);
+            let v = match op {
+                Some(op) => {
+                    let current = object.clone().index(index.clone()).map_err(to_runtime)?;
+                    let r = eval_expr(value, env)?;
+                    binary(op, current, r).map_err(to_runtime)?
                 }
-                Token::NotEqual => Type::not_equal(l, r),
-                Token::LessThan => Type::less_than(l, r),
-                Token::GreaterThan => Type::greater_than(l, r),
-                Token::LessThanEqual => Type::less_than_equal(l, r),
-                Token::GreaterThanEqual => Type::greater_than_equal(l, r),
-                _ => panic!("Unknown operator {:?}", op),
+                None => eval_expr(value, env)?,
+            };
+            object.set_index(index, v.clone()).map_err(to_runtime)?;
+            v
+        }
+        ExprKind::Unary { op, operand } => {
+            let value = eval_expr(operand, env)?;
+            let result = match op {
+                Token::Minus => value.negate(),
+                Token::Plus => value.unary_plus(),
+                Token::Bang | Token::Not => value.not(),
+                Token::Tilde => value.bit_not(),
+                _ => Err(ValueError::new(codes::INVALID_OPERANDS, format!("Unknown operator {}", op))),
+            };
+            result.map_err(|e| RuntimeError::new(e.code, e.message, &expr.span))?
+        }
+        ExprKind::Logical { left, op, right } => {
+            let name = if *op == Token::And { "logical and" } else { "logical or" };
+            let as_bool = |operand: &Expr| match eval_expr(operand, env)? {
+                Type::Bool(b) => Ok(b),
+                other => Err(RuntimeError::new(
+                    codes::INVALID_OPERANDS,
+                    format!("Invalid type for {}: {}", name, other.type_name()),
+                    &operand.span,
+                )),
+            };
+            // `and` stops at the first false, `or` at the first true
+            let l = as_bool(left)?;
+            let short_circuit = if *op == Token::And { !l } else { l };
+            if short_circuit {
+                Type::Bool(l)
+            } else {
+                Type::Bool(as_bool(right)?)
             }
         }
-        Expr::Call { callee, arguments } => {
-            let function = env
-                .borrow()
-                .get_function(callee)
-                .unwrap_or_else(|| panic!("Undefined function: {}", callee));
-
-            if arguments.len() != function.params.len() {
-                panic!(
-                    "Function '{}' expected {} arguments, got {}",
-                    callee,
-                    function.params.len(),
-                    arguments.len()
-                );
+        ExprKind::Binary { left, op, right } => {
+            let l = eval_expr(left, env)?;
+            let r = eval_expr(right, env)?;
+            binary(op, l, r).map_err(|e| RuntimeError::new(e.code, e.message, &expr.span))?
+        }
+        ExprKind::Call { callee, arguments } => {
+            let callee = match &callee.kind {
+                // a missing name is reported as an undefined function rather than variable
+                ExprKind::Var(name) => lookup(name, env).ok_or_else(|| {
+                    RuntimeError::undefined(codes::UNDEFINED_FUNCTION, "function", name, &expr.span, &env.borrow())
+                })?,
+                _ => eval_expr(callee, env)?,
+            };
+            let arg_values = eval_args(arguments, env)?;
+            return call_value(callee, arg_values, &expr.span);
+        }
+        ExprKind::Lambda { params, body } => Type::Function(Rc::new(Function {
+            name: "<lambda>".to_string(),
+            params: params.clone(),
+            body: Rc::clone(body),
+            closure: Rc::clone(env),
+        })),
+        ExprKind::Get { object, name } => {
+            let object = eval_expr(object, env)?;
+            let instance = as_instance(&object, "field access", &expr.span)?;
+            let instance = instance.borrow();
+            // a field, or else a method bound to the object
+            match instance.fields.get(name) {
+                Some(value) => value.clone(),
+                None => match instance.class.find_method(name) {
+                    Some(method) => Type::Function(Rc::new(method.bind(object.clone()))),
+                    None => return Err(undefined_field(&instance.class, name, &expr.span)),
+                },
             }
+        }
+        ExprKind::Set { object, name, op, value } => {
+            // the object is evaluated once, even for `p.age += 1`
+            let object = eval_expr(object, env)?;
+            let instance = as_instance(&object, "field assignment", &expr.span)?;
+            let current = instance.borrow().fields.get(name).cloned();
+            let Some(current) = current else {
+                return Err(undefined_field(&instance.borrow().class, name, &expr.span));
+            };
+            let v = match op {
+                Some(op) => {
+                    let r = eval_expr(value, env)?;
+                    binary(op, current, r).map_err(|e| RuntimeError::new(e.code, e.message, &expr.span))?
+                }
+                None => eval_expr(value, env)?,
+            };
+            instance.borrow_mut().fields.insert(name.clone(), v.clone());
+            v
+        }
+        ExprKind::Super(method) => {
+            // the parser only allows `super` in methods of a subclass, where both names are bound
+            let (Some(Type::Class(parent)), Some(object)) = (env.borrow().get("super"), env.borrow().get("self")) else {
+                return Err(RuntimeError::new(codes::SUPER_OUTSIDE_METHOD, "`super` used outside a method", &expr.span));
+            };
+            let Some(function) = parent.find_method(method) else {
+                return Err(undefined_field(&parent, method, &expr.span));
+            };
+            Type::Function(Rc::new(function.bind(object)))
+        }
+    };
+    Ok(value)
+}
 
-            let arg_values: Vec<Type> = arguments
-                .iter()
-                .map(|arg| eval_expr(arg, env))
-                .collect();
+/// The value of the variable `name`, or the builtin of that name if the script does not define one
+fn lookup(name: &str, env: &Rc<RefCell<Environment>>) -> Option<Type> {
+    env.borrow().get(name).or_else(|| builtins::lookup(name))
+}
 
-            let call_env = Rc::new(RefCell::new(Environment::new_scope(Rc::clone(&function.closure))));
-            for (param, value) in function.params.iter().cloned().zip(arg_values.into_iter()) {
-                call_env.borrow_mut().define(param, value);
-            }
+fn eval_args(arguments: &[Expr], env: &Rc<RefCell<Environment>>) -> Result<Vec<Type>, RuntimeError> {
+    arguments.iter().map(|arg| eval_expr(arg, env)).collect()
+}
+
+/// Error unless a call to `what` passes as many arguments as it takes
+fn check_arity(what: &str, expected: usize, got: usize, span: &Span) -> Result<(), RuntimeError> {
+    if expected == got {
+        return Ok(());
+    }
+    Err(RuntimeError::new(
+        codes::WRONG_ARGUMENT_COUNT,
+        format!("{} expected {} arguments, got {}", what, expected, got),
+        span,
+    ))
+}
+
+/// Call a function or class with already evaluated arguments
+fn call_value(callee: Type, args: Vec<Type>, call_site: &Span) -> Result<Type, RuntimeError> {
+    match callee {
+        Type::Function(function) => call_function(&function, args, call_site),
+        Type::Builtin(name) => builtins::call(name, args)
+            .expect("builtin values name a builtin")
+            .map_err(|e| RuntimeError::new(e.code, e.message, call_site)),
+        // calling a class makes a new instance of it
+        Type::Class(class) => instantiate(&class, args, call_site),
+        other => Err(RuntimeError::new(
+            codes::NOT_CALLABLE,
+            format!("Cannot call a value of type {}", other.type_name()),
+            call_site,
+        )),
+    }
+}
+
+/// How many Fig calls may be active at once; deeper recursion is reported instead of overflowing the native stack
+const MAX_CALL_DEPTH: usize = 1000;
+
+thread_local! {
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Run a user function; its name is shown in the call trace
+fn call_function(function: &Function, args: Vec<Type>, call_site: &Span) -> Result<Type, RuntimeError> {
+    check_arity(&format!("Function '{}'", function.name), function.params.len(), args.len(), call_site)?;
+    let depth = CALL_DEPTH.get();
+    if depth >= MAX_CALL_DEPTH {
+        return Err(RuntimeError::new(
+            codes::RECURSION_LIMIT,
+            format!("Maximum recursion depth exceeded calling '{}'", function.name),
+            call_site,
+        )
+        .with_help(format!("no more than {} calls can be active at once; check that the recursion reaches a base case", MAX_CALL_DEPTH)));
+    }
+    let call_env = Rc::new(RefCell::new(Environment::new_scope(Rc::clone(&function.closure))));
+    for (param, value) in function.params.iter().cloned().zip(args) {
+        call_env.borrow_mut().define(param, value).expect("a new scope has no constants");
+    }
+
+    CALL_DEPTH.set(depth + 1);
+    let result = eval_stmt(&function.body, Rc::clone(&call_env));
+    CALL_DEPTH.set(depth);
+    let result = result.map_err(|mut e| {
+        e.trace.push(Frame {
+            function: function.name.clone(),
+            call_site: call_site.clone(),
+        });
+        e
+    })?;
+    Ok(match result {
+        ExecResult::Return(val) => val,
+        ExecResult::Value(Some(val)) => val,
+        ExecResult::Value(None) => Type::Null,
+        ExecResult::Break(_) | ExecResult::Continue(_) => {
+            unreachable!("the parser rejects break and continue that would leave a function")
+        }
+    })
+}
+
+/// Make a new instance of `class`, running its `init` method with `args` if it has one
+fn instantiate(class: &Rc<Class>, args: Vec<Type>, call_site: &Span) -> Result<Type, RuntimeError> {
+    let mut fields = HashMap::new();
+    default_fields(class, &mut fields)?;
+    let object = Type::Instance(Rc::new(RefCell::new(Instance { class: Rc::clone(class), fields })));
+    match class.find_method("init") {
+        Some(init) => {
+            call_function(&init.bind(object.clone()), args, call_site)?;
+        }
+        None if !args.is_empty() => {
+            let message = format!("Class '{}' expected 0 arguments, got {}", class.name, args.len());
+            return Err(RuntimeError::new(codes::WRONG_ARGUMENT_COUNT, message, call_site)
+                .with_help(format!("define `fn init(self, ...)` in `{}` to take arguments", class.name)));
+        }
+        None => {}
+    }
+    Ok(object)
+}
 
-            match eval_stmt(&function.body, Rc::clone(&call_env)) {
-                ExecResult::Return(val) => val,
-                ExecResult::Value(Some(val)) => val,
-                ExecResult::Value(None) => Type::Null,
+/// Give each field of `class` its default value, parents first so a subclass can override them
+fn default_fields(class: &Class, fields: &mut HashMap<String, Type>) -> Result<(), RuntimeError> {
+    if let Some(parent) = &class.parent {
+        default_fields(parent, fields)?;
+    }
+    for (name, default) in &class.fields {
+        let value = match default {
+            Some(e) => eval_expr(e, &class.closure)?,
+            None => Type::Null,
+        };
+        fields.insert(name.clone(), value);
+    }
+    Ok(())
+}
+
+fn as_instance(value: &Type, operation: &str, span: &Span) -> Result<Rc<RefCell<Instance>>, RuntimeError> {
+    match value {
+        Type::Instance(instance) => Ok(Rc::clone(instance)),
+        other => Err(RuntimeError::new(
+            codes::INVALID_OPERANDS,
+            format!("Invalid type for {}: {}", operation, other.type_name()),
+            span,
+        )),
+    }
+}
+
+/// Error for a field or method `class` does not declare, suggesting the closest one it does
+fn undefined_field(class: &Class, name: &str, span: &Span) -> RuntimeError {
+    if class.find_method(name).is_some() {
+        let message = format!("Undefined field: {}.{} is a method, not a field", class.name, name);
+        return RuntimeError::new(codes::UNDEFINED_FIELD, message, span)
+            .with_help(format!("call it with `.{}(...)`", name));
+    }
+    let error = RuntimeError::new(
+        codes::UNDEFINED_FIELD,
+        format!("Undefined field: {} has no field or method {}", class.name, name),
+        span,
+    );
+    match did_you_mean(name, class.field_names().into_iter().chain(class.method_names())) {
+        Some(suggestion) => error.with_help(format!("did you mean `{}`?", suggestion)),
+        None => error,
+    }
+}
+
+/// Apply a binary operator; also used by compound assignment
+fn binary(op: &Token, l: Type, r: Type) -> Result<Type, ValueError> {
+    match op {
+        Token::Plus  => Type::add(l, r),
+        Token::Minus => Type::subtract(l, r),
+        Token::Star  => Type::multiply(l, r),
+        Token::Slash => Type::divide(l, r),
+        Token::DblSlash => Type::floor_divide(l, r),
+        Token::Percent => Type::modulo(l, r),
+        Token::DblStar => Type::power(l, r),
+        Token::Ampersand => Type::bit_and(l, r),
+        Token::Pipe => Type::bit_or(l, r),
+        Token::Caret => Type::bit_xor(l, r),
+        Token::ShiftLeft => Type::shift_left(l, r),
+        Token::ShiftRight => Type::shift_right(l, r),
+        Token::DblEqual => Type::equal(l, r),
+        Token::Range => {
+            match l {
+                Type::Int(n) if n >= 0 => Ok(Type::Range(n)),
+                Type::Int(_) => Err(ValueError::new(codes::INVALID_RANGE, "Range argument must be non-negative")),
+                _ => Err(ValueError::new(codes::INVALID_RANGE, "Range argument must be an integer")),
             }
         }
+        Token::NotEqual => Type::not_equal(l, r),
+        Token::LessThan => Type::less_than(l, r),
+        Token::GreaterThan => Type::greater_than(l, r),
+        Token::LessThanEqual => Type::less_than_equal(l, r),
+        Token::GreaterThanEqual => Type::greater_than_equal(l, r),
+        Token::In => r.contains(l),
+        Token::Is => l.instance_of(r),
+        _ => Err(ValueError::new(codes::INVALID_OPERANDS, format!("Unknown operator {}", op))),
     }
 }
 
 // Execute a single statement, updating the environment.
-fn eval_stmt(stmt: &Stmt, env: Rc<RefCell<Environment>>) -> ExecResult {
-    match stmt {
-        Stmt::ForStmt { var_name, range, body } => {
-            let range_value = eval_expr(range, &env);
-            match range_value {
-                Type::Range(n) => {
-                    let mut last_val: Option<Type> = None;
-                    for i in 0..n {
-                        let iter_env = Rc::new(RefCell::new(Environment::new_scope(Rc::clone(&env))));
-                        iter_env.borrow_mut().define(var_name.clone(), Type::Int(i));
-                        match eval_stmt(body.as_ref(), Rc::clone(&iter_env)) {
-                            ExecResult::Return(val) => return ExecResult::Return(val),
-                            ExecResult::Value(value) => {
-                                if let Some(v) = value {
-                                    last_val = Some(v);
-                                }
-                            }
+fn eval_stmt(stmt: &Stmt, env: Rc<RefCell<Environment>>) -> Result<ExecResult, RuntimeError> {
+    let result = match &stmt.kind {
+        StmtKind::ForStmt { label, var_name, range, body } => {
+            let items: Box<dyn Iterator<Item = Type>> = match eval_expr(range, &env)? {
+                Type::Range(n) => Box::new((0..n).map(Type::Int)),
+                Type::Str(s) => Box::new(s.chars().collect::<Vec<char>>().into_iter().map(Type::Char)),
+                // iterate over a snapshot, so the body can change the list
+                Type::List(items) => Box::new(items.borrow().clone().into_iter()),
+                Type::Map(map) => Box::new(map.borrow().keys().into_iter()),
+                other => {
+                    return Err(RuntimeError::new(
+                        codes::NOT_ITERABLE,
+                        format!("Expected range, string, list or map in for loop, got {}", other.type_name()),
+                        &range.span,
+                    ))
+                }
+            };
+            let mut last_val: Option<Type> = None;
+            for item in items {
+                let iter_env = Rc::new(RefCell::new(Environment::new_scope(Rc::clone(&env))));
+                iter_env.borrow_mut().define(var_name.clone(), item).expect("a new scope has no constants");
+                match eval_stmt(body.as_ref(), Rc::clone(&iter_env))? {
+                    ExecResult::Value(value) => {
+                        if let Some(v) = value {
+                            last_val = Some(v);
                         }
                     }
-                    ExecResult::Value(last_val)
+                    ExecResult::Break(target) if targets(label, &target) => break,
+                    ExecResult::Continue(target) if targets(label, &target) => continue,
+                    other => return Ok(other),
                 }
-                _ => panic!("Expected range value in for loop"),
             }
+            ExecResult::Value(last_val)
         }
-        Stmt::VarDecl { name, value } => {
-            let v = eval_expr(value, &env);
-            let defined = env.borrow().is_defined(name);
-            if defined {
-                env.borrow_mut().update(name.clone(), v.clone());
-            } else {
-                env.borrow_mut().define(name.clone(), v);
+        StmtKind::While { label, condition, body } => {
+            let mut last_val: Option<Type> = None;
+            loop {
+                match eval_expr(condition, &env)? {
+                    Type::Bool(true) => {}
+                    Type::Bool(false) => break,
+                    other => {
+                        return Err(RuntimeError::new(
+                            codes::NON_BOOLEAN_CONDITION,
+                            format!("Condition must be a boolean, got {}", other.type_name()),
+                            &condition.span,
+                        ))
+                    }
+                }
+                let iter_env = Rc::new(RefCell::new(Environment::new_scope(Rc::clone(&env))));
+                match eval_stmt(body.as_ref(), iter_env)? {
+                    ExecResult::Value(value) => {
+                        if let Some(v) = value {
+                            last_val = Some(v);
+                        }
+                    }
+                    ExecResult::Break(target) if targets(label, &target) => break,
+                    ExecResult::Continue(target) if targets(label, &target) => continue,
+                    other => return Ok(other),
+                }
             }
+            ExecResult::Value(last_val)
+        }
+        StmtKind::VarDecl { name, value, constant } => {
+            // always a new binding in this scope, shadowing any outer variable of the same name
+            let v = eval_expr(value, &env)?;
+            declare(&env, name, v, *constant, &stmt.span)?;
             ExecResult::Value(None)
         }
-        Stmt::Block(stmts) => {
+        StmtKind::Block(stmts) => {
             let mut last_val: Option<Type> = None;
             let block_env = Rc::new(RefCell::new(Environment::new_scope(Rc::clone(&env))));
             for stmt in stmts {
-                match eval_stmt(stmt, Rc::clone(&block_env)) {
-                    ExecResult::Return(val) => return ExecResult::Return(val),
+                match eval_stmt(stmt, Rc::clone(&block_env))? {
                     ExecResult::Value(value) => {
                         if let Some(v) = value {
                             last_val = Some(v);
                         }
                     }
+                    // return, break and continue all stop the rest of the block
+                    other => return Ok(other),
                 }
             }
             ExecResult::Value(last_val)
         }
-        Stmt::ExprStmt(expr) => ExecResult::Value(Some(eval_expr(expr, &env))),
-        Stmt::PrntStmt(expr) => {
-            let value = eval_expr(expr, &env);
+        StmtKind::ExprStmt(expr) => ExecResult::Value(Some(eval_expr(expr, &env)?)),
+        StmtKind::PrntStmt(expr) => {
+            let value = eval_expr(expr, &env)?;
             println!("{}", value);
             ExecResult::Value(None)
         }
-        Stmt::IfStmt { condition, then_branch, else_branch } => {
-            let cond_value = eval_expr(condition, &env);
+        StmtKind::IfStmt { condition, then_branch, else_branch } => {
+            let cond_value = eval_expr(condition, &env)?;
             match cond_value {
                 Type::Bool(true) => {
                     let then_env = Rc::new(RefCell::new(Environment::new_scope(Rc::clone(&env))));
-                    match eval_stmt(then_branch.as_ref(), then_env) {
-                        ExecResult::Return(val) => return ExecResult::Return(val),
-                        ExecResult::Value(value) => return ExecResult::Value(value),
-                    }
+                    eval_stmt(then_branch.as_ref(), then_env)?
                 }
                 Type::Bool(false) => {
                     if let Some(else_stmt) = else_branch {
                         let else_env = Rc::new(RefCell::new(Environment::new_scope(Rc::clone(&env))));
-                        match eval_stmt(else_stmt.as_ref(), else_env) {
-                            ExecResult::Return(val) => return ExecResult::Return(val),
-                            ExecResult::Value(value) => return ExecResult::Value(value),
-                        }
+                        return eval_stmt(else_stmt.as_ref(), else_env);
                     }
                     ExecResult::Value(None)
                 }
-                _ => panic!("Condition must be a boolean, got {:?}", cond_value),
+                _ => {
+                    return Err(RuntimeError::new(
+                        codes::NON_BOOLEAN_CONDITION,
+                        format!("Condition must be a boolean, got {}", cond_value.type_name()),
+                        &condition.span,
+                    ))
+                }
             }
         }
-        Stmt::Function { name, params, body } => {
+        StmtKind::Function { name, params, body } => {
             let function = Function {
+                name: name.clone(),
                 params: params.clone(),
-                body: (*body.clone()),
+                body: Rc::clone(body),
                 closure: Rc::clone(&env),
             };
-            env.borrow_mut().define_function(name.clone(), function);
+            declare(&env, name, Type::Function(Rc::new(function)), false, &stmt.span)?;
             ExecResult::Value(None)
         }
-        Stmt::Return(expr) => {
-            let value = expr
-                .as_ref()
-                .map(|e| eval_expr(e, &env))
-                .unwrap_or(Type::Null);
+        StmtKind::Class { name, parent, fields, methods } => {
+            let parent = match parent {
+                Some(expr) => match eval_expr(expr, &env)? {
+                    Type::Class(class) => Some(class),
+                    other => {
+                        return Err(RuntimeError::new(
+                            codes::NOT_A_CLASS,
+                            format!("Cannot inherit from {}, which is not a class", other.type_name()),
+                            &expr.span,
+                        ));
+                    }
+                },
+                None => None,
+            };
+            // methods of a subclass see its parent as `super`
+            let closure = match &parent {
+                Some(parent) => {
+                    let mut scope = Environment::new_scope(Rc::clone(&env));
+                    scope.define("super".to_string(), Type::Class(Rc::clone(parent))).expect("a new scope has no constants");
+                    Rc::new(RefCell::new(scope))
+                }
+                None => Rc::clone(&env),
+            };
+            let methods = methods
+                .iter()
+                .filter_map(|method| match &method.kind {
+                    StmtKind::Function { name: method, params, body } => Some((
+                        method.clone(),
+                        Rc::new(Function {
+                            name: format!("{}.{}", name, method),
+                            params: params.clone(),
+                            body: Rc::clone(body),
+                            closure: Rc::clone(&closure),
+                        }),
+                    )),
+                    _ => None,
+                })
+                .collect();
+            let class = Class::new(name.clone(), parent, fields.clone(), methods, Rc::clone(&env));
+            declare(&env, name, Type::Class(Rc::new(class)), false, &stmt.span)?;
+            ExecResult::Value(None)
+        }
+        StmtKind::Return(expr) => {
+            let value = match expr {
+                Some(e) => eval_expr(e, &env)?,
+                None => Type::Null,
+            };
             ExecResult::Return(value)
         }
-    }
+        StmtKind::Break(label) => ExecResult::Break(label.clone()),
+        StmtKind::Continue(label) => ExecResult::Continue(label.clone()),
+    };
+    Ok(result)
+}
+
+/// Declare `name` in the current scope, which fails if it already holds a constant of that name
+fn declare(env: &Rc<RefCell<Environment>>, name: &str, value: Type, constant: bool, span: &Span) -> Result<(), RuntimeError> {
+    let declared = if constant {
+        env.borrow_mut().define_const(name.to_string(), value)
+    } else {
+        env.borrow_mut().define(name.to_string(), value)
+    };
+    declared.map_err(|_| {
+        let mut error = RuntimeError::new(codes::ASSIGN_TO_CONSTANT, format!("Cannot redeclare constant: {}", name), span);
+        error.label = Some("already declared as a constant in this scope".to_string());
+        error.with_help("use a different name, or shadow it inside a block")
+    })
 }
 
 /// Run all statements and return the last expression's value.
-pub fn eval_program(stmts: &[Stmt]) -> Type {
+pub fn eval_program(stmts: &[Stmt]) -> Result<Type, RuntimeError> {
     let env = Rc::new(RefCell::new(Environment::new()));
     let mut last_val: Option<Type> = None;
 
     for stmt in stmts {
-        match eval_stmt(stmt, Rc::clone(&env)) {
-            ExecResult::Return(val) => return val,
+        match eval_stmt(stmt, Rc::clone(&env))? {
+            ExecResult::Return(val) => return Ok(val),
             ExecResult::Value(value) => {
                 if let Some(v) = value {
                     last_val = Some(v);
                 }
             }
+            ExecResult::Break(_) | ExecResult::Continue(_) => unreachable!("the parser rejects break and continue outside loops"),
         }
     }
 
-    last_val.unwrap_or(Type::Null)
+    Ok(last_val.unwrap_or(Type::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    /// Parse and run the program `src`, returning the value of its last expression statement
+    fn run(src: &str) -> Result<Type, RuntimeError> {
+        let program = Parser::new(Lexer::new(src, "<test>")).parse().expect("parse error");
+        eval_program(&program)
+    }
+
+    fn value(src: &str) -> Type {
+        run(src).unwrap_or_else(|e| panic!("runtime error in {src:?}: {}", e.message))
+    }
+
+    #[test]
+    fn and_or_skip_the_right_side_once_the_left_decides() {
+        assert_eq!(value("var x = 0; x != 0 and 10 / x > 1;"), Type::Bool(false));
+        assert_eq!(value("var x = 0; x == 0 or 10 / x > 1;"), Type::Bool(true));
+        assert_eq!(run("var x = 0; x == 0 and 10 / x > 1;").unwrap_err().code, codes::DIVISION_BY_ZERO);
+        assert_eq!(run("var x = 0; x != 0 || 10 / x > 1;").unwrap_err().code, codes::DIVISION_BY_ZERO);
+    }
+
+    #[test]
+    fn labelled_break_leaves_the_outer_loop() {
+        let source = "var seen = \"\";\nouter: for i in range(3) {\n    for j in range(3) {\n        if (i == 1 and j == 1) { break outer; }\n        seen += \"${i}${j} \";\n    }\n}\nseen;";
+        assert_eq!(value(source), Type::Str("00 01 02 10 ".to_string()));
+        let source = "var n = 0;\nouter: while (true) {\n    while (true) {\n        n += 1;\n        if (n == 3) { break outer; }\n    }\n}\nn;";
+        assert_eq!(value(source), Type::Int(3));
+    }
+
+    #[test]
+    fn labelled_continue_starts_the_next_outer_iteration() {
+        let source = "var seen = \"\";\nouter: for i in range(3) {\n    for j in range(3) {\n        if (j == 1) { continue outer; }\n        seen += \"${i}${j} \";\n    }\n    seen += \"| \";\n}\nseen;";
+        assert_eq!(value(source), Type::Str("00 10 20 ".to_string()));
+        let source = "var i = 0;\nvar odd = 0;\nouter: while (i < 5) {\n    i += 1;\n    for j in range(1) {\n        if (i % 2 == 0) { continue outer; }\n    }\n    odd += 1;\n}\nodd;";
+        assert_eq!(value(source), Type::Int(3));
+    }
+
+    #[test]
+    fn return_leaves_every_loop_in_the_function() {
+        let source = "fn find(grid, target) {\n    for row in grid {\n        var i = 0;\n        while (i < len(row)) {\n            if (row[i] == target) { return i; }\n            i += 1;\n        }\n    }\n    return -1;\n}\n[find([[1, 2], [3, 4]], 4), find([[1]], 5)];";
+        assert_eq!(value(source).to_string(), "[1, -1]");
+    }
+
+    #[test]
+    fn assigning_an_undeclared_name_is_an_error() {
+        let error = run("var total = 0;\ntotl = 5;").unwrap_err();
+        assert_eq!(error.code, codes::UNDEFINED_VARIABLE);
+        assert_eq!((error.span.line, error.span.column), (2, 1));
+        assert_eq!(error.help, ["did you mean `total`?"]);
+        let error = run("if (true) { count += 1; }").unwrap_err();
+        assert_eq!(error.code, codes::UNDEFINED_VARIABLE);
+        assert_eq!(error.help, ["use `var count = ...` to declare a new variable"]);
+    }
+
+    #[test]
+    fn shadowing_leaves_the_outer_variable_alone() {
+        assert_eq!(value("var x = 1;\nif (true) { var x = 2; x = 3; }\nx;"), Type::Int(1));
+        assert_eq!(value("var x = 1;\nfn f() { var x = 5; x += 1; return x; }\nf() * 10 + x;"), Type::Int(61));
+        assert_eq!(value("var x = 1;\nfor i in range(2) { var x = i; }\nx;"), Type::Int(1));
+        assert_eq!(value("var x = 1;\nif (true) { x = 2; }\nx;"), Type::Int(2), "no `var` assigns the outer one");
+    }
+
+    #[test]
+    #[should_panic(expected = "the parser rejects break and continue")]
+    fn a_break_escaping_a_function_is_a_bug() {
+        // `parse_stmt` records the error but still returns the statement
+        let stray = Parser::new(Lexer::new("break;", "<test>")).parse_stmt().expect("parse error");
+        let function = Function {
+            name: "f".to_string(),
+            params: Vec::new(),
+            body: Rc::new(stray),
+            closure: Rc::new(RefCell::new(Environment::new())),
+        };
+        let _ = call_function(&function, Vec::new(), &function.body.span);
+    }
 }
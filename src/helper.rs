@@ -1,53 +1,235 @@
-use crate::token::Token;
+use crate::token::{Span, Token};
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     Number(i64),
     Float(f64),
     String(String),
+    Interpolation(Vec<Expr>), // string literal with `${...}`; literal text is `String` parts
+    Char(char),
     Bool(bool),
+    Unary {
+        op: Token, // `-`, `+`, `!`, `not` or `~`
+        operand: Box<Expr>,
+    },
     Binary {
         left: Box<Expr>,
         op: Token,
         right: Box<Expr>,
     },
+    Logical {
+        left: Box<Expr>,
+        op: Token, // `And` or `Or`; the right side is only evaluated when needed
+        right: Box<Expr>,
+    },
+    List(Vec<Expr>),        // `[a, b, c]`
+    Map(Vec<(Expr, Expr)>), // `{key: value, ...}`
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+    },
+    Slice {
+        object: Box<Expr>,
+        start: Option<Box<Expr>>, // `xs[:b]` starts at 0
+        end: Option<Box<Expr>>,   // `xs[a:]` runs to the end
+    },
     Var(String), // Variable reference
+    Assign {
+        name: String,      // must already be declared; `var` declares
+        op: Option<Token>, // `Plus` for `+=` and so on, `None` for plain `=`
+        value: Box<Expr>,
+    },
+    SetIndex {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        op: Option<Token>, // as for `Assign`
+        value: Box<Expr>,
+    },
+    Get {
+        object: Box<Expr>, // `object.name`
+        name: String,
+    },
+    Set {
+        object: Box<Expr>,
+        name: String,
+        op: Option<Token>, // as for `Assign`
+        value: Box<Expr>,
+    },
+    Super(String), // `super.method`, the parent's version of a method bound to `self`
     Call {
-        callee: String,
+        callee: Box<Expr>, // anything that evaluates to a function or class; `object.method(...)` calls a bound method
         arguments: Vec<Expr>,
     },
+    Lambda {
+        params: Vec<String>, // `fn(a, b) { ... }`, or `x => expr` with a body of `return expr;`
+        body: Rc<Stmt>,
+    },
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
 }
 
+/// A field declared in a class body and its default value, if it has one
+pub type Field = (String, Option<Expr>);
+
 #[derive(Debug, Clone)]
-pub enum Stmt {
-    VarDecl { name: String, value: Expr },
+pub enum StmtKind {
+    VarDecl { name: String, value: Expr, constant: bool }, // `const` when `constant`
     ExprStmt(Expr),
     PrntStmt(Expr),
     Block(Vec<Stmt>),
     IfStmt { condition: Expr, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>> },
-    ForStmt { var_name: String, range: Expr, body: Box<Stmt> },
-    Function { name: String, params: Vec<String>, body: Box<Stmt> },
+    ForStmt { label: Option<String>, var_name: String, range: Expr, body: Box<Stmt> },
+    While { label: Option<String>, condition: Expr, body: Box<Stmt> },
+    Break(Option<String>),    // optional label of the loop to leave
+    Continue(Option<String>), // optional label of the loop to continue
+    Function { name: String, params: Vec<String>, body: Rc<Stmt> },
+    Class {
+        name: String,
+        parent: Option<Expr>, // `class Manager(Employee)`, a `Var` naming the class to inherit from
+        fields: Vec<Field>,
+        methods: Vec<Stmt>, // `Function`s
+    },
     Return(Option<Expr>),
 }
 
-#[derive(Debug)]
-pub enum BuiltInFunction {
-    Range(i64),
-}#[derive(PartialEq, PartialOrd)]
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Stmt { kind, span }
+    }
+}
+
+/// Binding power of operators for the Pratt parser, loosest first.
+///
+/// | level      | operators                   | associativity |
+/// |------------|-----------------------------|---------------|
+/// | Assignment | `=` `+=` `-=` ...           | right         |
+/// | Or         | `or` `\|\|`                 | left          |
+/// | And        | `and` `&&`                  | left          |
+/// | Equality   | `==` `!=`                   | left          |
+/// | Comparison | `<` `>` `<=` `>=` `in` `is` | left          |
+/// | BitOr      | `\|`                        | left          |
+/// | BitXor     | `^`                         | left          |
+/// | BitAnd     | `&`                         | left          |
+/// | Shift      | `<<` `>>`                   | left          |
+/// | Sum        | `+` `-`                     | left          |
+/// | Product    | `*` `/` `//` `%`            | left          |
+/// | Power      | `**`                        | right         |
+/// | Prefix     | `-` `+` `!` `not` `~`       | prefix        |
+/// | Call       | `f(...)` `xs[i]` `.`        | postfix       |
+///
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum Precedence {
     Lowest,
-    Sum,     // + -
-    Product, // * /
-    DblEqual,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Sum,
+    Product,
+    Power,
+    Prefix,
+    Call,
+}
+
+impl Precedence {
+    /// The level just below this one.
+    /// A right-associative operator parses its right operand here, so `a op b op c` nests to the right.
+    pub fn lower(self) -> Precedence {
+        match self {
+            Precedence::Lowest | Precedence::Assignment => Precedence::Lowest,
+            Precedence::Or => Precedence::Assignment,
+            Precedence::And => Precedence::Or,
+            Precedence::Equality => Precedence::And,
+            Precedence::Comparison => Precedence::Equality,
+            Precedence::BitOr => Precedence::Comparison,
+            Precedence::BitXor => Precedence::BitOr,
+            Precedence::BitAnd => Precedence::BitXor,
+            Precedence::Shift => Precedence::BitAnd,
+            Precedence::Sum => Precedence::Shift,
+            Precedence::Product => Precedence::Sum,
+            Precedence::Power => Precedence::Product,
+            Precedence::Prefix => Precedence::Power,
+            Precedence::Call => Precedence::Prefix,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
 }
 
+/// Precedence of `tok` when it follows an operand; `Lowest` for anything that is not an infix or postfix operator
 pub fn precedence(tok: &Token) -> Precedence {
     match tok {
+        Token::Equal => Precedence::Assignment,
+        tok if compound_operator(tok).is_some() => Precedence::Assignment,
+        Token::Or => Precedence::Or,
+        Token::And => Precedence::And,
+        Token::DblEqual | Token::NotEqual => Precedence::Equality,
+        Token::LessThan | Token::GreaterThan | Token::LessThanEqual | Token::GreaterThanEqual | Token::In | Token::Is => {
+            Precedence::Comparison
+        }
+        Token::Pipe => Precedence::BitOr,
+        Token::Caret => Precedence::BitXor,
+        Token::Ampersand => Precedence::BitAnd,
+        Token::ShiftLeft | Token::ShiftRight => Precedence::Shift,
         Token::Plus | Token::Minus => Precedence::Sum,
-        Token::Star | Token::Slash => Precedence::Product,
-        Token::DblEqual | Token::NotEqual => Precedence::DblEqual,
-        Token::LessThan | Token::GreaterThan | Token::LessThanEqual | Token::GreaterThanEqual => Precedence::DblEqual,
-        Token::Range => Precedence::Lowest,
+        Token::Star | Token::Slash | Token::DblSlash | Token::Percent => Precedence::Product,
+        Token::DblStar => Precedence::Power,
+        Token::LParen | Token::LBracket | Token::Dot => Precedence::Call,
         _ => Precedence::Lowest,
     }
 }
+
+/// How a chain of operators at the same level groups: `a - b - c` is `(a - b) - c`
+pub fn associativity(tok: &Token) -> Associativity {
+    match tok {
+        Token::Equal | Token::DblStar => Associativity::Right,
+        tok if compound_operator(tok).is_some() => Associativity::Right,
+        _ => Associativity::Left,
+    }
+}
+
+/// The binary operator a compound assignment applies: `Plus` for `+=`
+pub fn compound_operator(tok: &Token) -> Option<Token> {
+    let op = match tok {
+        Token::PlusEqual => Token::Plus,
+        Token::MinusEqual => Token::Minus,
+        Token::StarEqual => Token::Star,
+        Token::SlashEqual => Token::Slash,
+        Token::PercentEqual => Token::Percent,
+        Token::DblStarEqual => Token::DblStar,
+        Token::DblSlashEqual => Token::DblSlash,
+        Token::AmpersandEqual => Token::Ampersand,
+        Token::PipeEqual => Token::Pipe,
+        Token::CaretEqual => Token::Caret,
+        Token::ShiftLeftEqual => Token::ShiftLeft,
+        Token::ShiftRightEqual => Token::ShiftRight,
+        _ => return None,
+    };
+    Some(op)
+}
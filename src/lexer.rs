@@ -1,36 +1,98 @@
-use crate::token::Token;
+use crate::codes;
+use crate::diagnostics::Diagnostic;
+use crate::token::{Span, SpannedToken, StrPart, Token};
+use std::rc::Rc;
 // use crate::helper::Type;
 
 pub struct Lexer {
     src: Vec<char>, // Source code as a vector of characters
     pos: usize,     // Current position in the source code
+    file: Rc<str>,  // Name of the file being lexed, copied into every span
+    byte_pos: usize, // Byte offset of `pos` in the original source
+    line: usize,     // 1-based line of `pos`
+    column: usize,   // 1-based column of `pos`
+    token_start: (usize, usize, usize), // line, column and byte offset of the token being read
+    errors: Vec<Diagnostic>,            // problems found so far, one per `Token::Illegal`
 }
 
 impl Lexer {
-    pub fn new(input: &str) -> Self {
+    /// Create a lexer whose spans report `file` as their origin
+    pub fn new(input: &str, file: &str) -> Self {
+        Lexer::at(input, Rc::from(file), (1, 1, 0))
+    }
+
+    /// Create a lexer for a snippet that starts at `(line, column, byte offset)` of `file`,
+    /// such as the code inside a `${...}` interpolation
+    pub fn at(input: &str, file: Rc<str>, start: (usize, usize, usize)) -> Self {
+        let (line, column, byte_pos) = start;
         Lexer {
             src: input.chars().collect(),
             pos: 0,
+            file,
+            byte_pos,
+            line,
+            column,
+            token_start: start,
+            errors: Vec::new(),
         }
     }
 
+    /// Hand over the errors recorded so far
+    pub fn take_errors(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.errors)
+    }
+
+    // Line, column and byte offset of `pos`, to build a span from later
+    fn mark(&self) -> (usize, usize, usize) {
+        (self.line, self.column, self.byte_pos)
+    }
+
+    // Span from the start of the current token up to `pos`
+    fn token_span(&self) -> Span {
+        self.span_since(self.token_start)
+    }
+
+    // Span from a `mark` up to `pos`
+    fn span_since(&self, (line, column, start): (usize, usize, usize)) -> Span {
+        Span {
+            file: Rc::clone(&self.file),
+            line,
+            column,
+            start,
+            end: self.byte_pos,
+        }
+    }
+
+    // Record an error covering the current token and return the `Illegal` token for it
+    fn illegal(&mut self, c: char, error: Diagnostic) -> Token {
+        self.errors.push(error);
+        Token::Illegal(c)
+    }
+
     // Peek the next character without consuming it.
     // Returns None if at the end of input.
     fn peek(&self) -> Option<char> {
         self.src.get(self.pos).copied()
     }
 
-    // // Like peek, but looks at the next next character instead
-    // fn peek_next(&self) -> Option<char> {
-    //     self.src.get(self.pos + 1).copied()
-    // }
+    // Like peek, but looks at the next next character instead
+    fn peek_next(&self) -> Option<char> {
+        self.src.get(self.pos + 1).copied()
+    }
 
     // Advance to the next character and return it.
     // Returns None if at the end of input.
     fn advance(&mut self) -> Option<char> {
         let ch = self.peek();
-        if ch.is_some() {
+        if let Some(c) = ch {
             self.pos += 1;
+            self.byte_pos += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
         }
         ch
     }
@@ -60,10 +122,20 @@ impl Lexer {
         }
     }
 
-    // Get the next token from the input.
-    // then we find out what kind of token that is, and return it.
-    pub fn next_token(&mut self) -> Token {
+    // Get the next token from the input, along with the span it covers.
+    pub fn next_token(&mut self) -> SpannedToken {
         self.skip_whitespace();
+        self.token_start = self.mark();
+        let token = self.scan_token();
+        SpannedToken {
+            token,
+            span: self.token_span(),
+        }
+    }
+
+    // Read the characters of one token.
+    // then we find out what kind of token that is, and return it.
+    fn scan_token(&mut self) -> Token {
         let ch = self.advance();
         // println!("Lexer: Read character: {:?}", ch);
 
@@ -72,28 +144,36 @@ impl Lexer {
             Some(')') => Token::RParen,
             Some('{') => Token::LBrace,
             Some('}') => Token::RBrace,
-            Some('+') => Token::Plus,
-            Some('-') => {
-                match self.peek() {
-                    Some(c) if c.is_ascii_digit() => {
-                        // Negative number: parse as number with '-' prefix
-                        let next_digit = self.advance().unwrap();
-                        let num_token = self.read_number(next_digit);
-                        match num_token {
-                            Token::Number(n) => Token::Number(-n),
-                            Token::Float(f) => Token::Float(-f),
-                            other => other,
-                        }
-                    }
-                    _ => Token::Minus,
+            Some('[') => Token::LBracket,
+            Some(']') => Token::RBracket,
+            Some('+') => self.compound(Token::Plus, Token::PlusEqual),
+            Some('-') => self.compound(Token::Minus, Token::MinusEqual), // negative numbers are unary minus applied to a literal
+            Some('*') => {
+                if self.peek() == Some('*') {
+                    self.advance(); // consume the second '*'
+                    self.compound(Token::DblStar, Token::DblStarEqual)
+                } else {
+                    self.compound(Token::Star, Token::StarEqual)
                 }
-            }
-            Some('*') => Token::Star,
-            Some('/') => Token::Slash,
+            },
+            Some('/') => {
+                if self.peek() == Some('/') {
+                    self.advance(); // consume the second '/'
+                    self.compound(Token::DblSlash, Token::DblSlashEqual)
+                } else {
+                    self.compound(Token::Slash, Token::SlashEqual)
+                }
+            },
+            Some('%') => self.compound(Token::Percent, Token::PercentEqual),
+            Some('^') => self.compound(Token::Caret, Token::CaretEqual),
+            Some('~') => Token::Tilde,
             Some('=') => {
                 if self.peek() == Some('=') {
                     self.advance(); // consume the second '='
                     Token::DblEqual
+                } else if self.peek() == Some('>') {
+                    self.advance(); // consume the '>'
+                    Token::FatArrow
                 } else {
                     Token::Equal
                 }
@@ -103,7 +183,23 @@ impl Lexer {
                     self.advance(); // consume the '='
                     Token::NotEqual
                 } else {
-                    Token::Illegal('!')
+                    Token::Bang
+                }
+            },
+            Some('&') => {
+                if self.peek() == Some('&') {
+                    self.advance(); // consume the second '&'
+                    Token::And
+                } else {
+                    self.compound(Token::Ampersand, Token::AmpersandEqual)
+                }
+            },
+            Some('|') => {
+                if self.peek() == Some('|') {
+                    self.advance(); // consume the second '|'
+                    Token::Or
+                } else {
+                    self.compound(Token::Pipe, Token::PipeEqual)
                 }
             },
             Some(',') => Token::Comma,
@@ -111,6 +207,9 @@ impl Lexer {
                 if self.peek() == Some('=') {
                     self.advance(); // consume the '='
                     Token::LessThanEqual
+                } else if self.peek() == Some('<') {
+                    self.advance(); // consume the second '<'
+                    self.compound(Token::ShiftLeft, Token::ShiftLeftEqual)
                 } else {
                     Token::LessThan
                 }
@@ -119,13 +218,24 @@ impl Lexer {
                 if self.peek() == Some('=') {
                     self.advance(); // consume the '='
                     Token::GreaterThanEqual
+                } else if self.peek() == Some('>') {
+                    self.advance(); // consume the second '>'
+                    self.compound(Token::ShiftRight, Token::ShiftRightEqual)
                 } else {
                     Token::GreaterThan
                 }
             },
-            Some('"') => self.read_string(),
+            Some('"') => self.read_string(false),
+            Some('\'') => self.read_char(),
+            Some('r') if self.peek() == Some('"') => {
+                self.advance(); // consume the opening quote of a raw string
+                self.read_string(true)
+            }
             Some(';') => Token::Semicolon,
+            Some(':') => Token::Colon,
             Some(c) if c.is_ascii_digit() => self.read_number(c),
+            Some('.') if self.peek().is_some_and(|c| c.is_ascii_digit()) => self.read_number('.'),
+            Some('.') => Token::Dot,
             None => Token::EOF,
             Some(c) => {
                 if c.is_alphanumeric() {
@@ -146,64 +256,150 @@ impl Lexer {
                     }
                 } else {
                     // If we reach here, it's an illegal character
-                    Token::Illegal(c)
+                    let error = Diagnostic::error(codes::UNEXPECTED_CHARACTER, format!("Unexpected character '{}'", c), self.token_span())
+                        .with_label("not valid in Fig source");
+                    self.illegal(c, error)
                 }
             },
         }
     }
 
     // we read a number, which can be multiple digits.
-    // If it has a decimal point, we treat it as a float.
+    // `0x`, `0b` and `0o` prefixes give hex, binary and octal integers, `_` separates digits,
+    // and a fraction or exponent (`1.5`, `.5`, `1e-3`) makes it a float.
     fn read_number(&mut self, first: char) -> Token {
+        let radix = match (first, self.peek()) {
+            ('0', Some('x' | 'X')) => 16,
+            ('0', Some('b' | 'B')) => 2,
+            ('0', Some('o' | 'O')) => 8,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.advance(); // consume the radix letter
+            let digits = self.read_digits(radix);
+            return match self.number_end() {
+                Some(error) => error,
+                None if digits.is_empty() || digits.ends_with('_') => self.invalid_number("expected digits after the prefix"),
+                None => match i64::from_str_radix(&digits.replace('_', ""), radix) {
+                    Ok(n) => Token::Number(n),
+                    Err(_) => self.number_out_of_range("Int"),
+                },
+            };
+        }
+
         let mut result = first.to_string();
-        let is_float = false;
-        while let Some(c) = self.peek() {
-            if c.is_ascii_digit() {
-                result.push(self.advance().unwrap());
-            } else {
-                if c == '.' {
-                    if is_float {
-                        // If we already saw a dot, this is an illegal number
-                        println!("Illegal number: multiple decimal points");
-                        return Token::Illegal(c);
-                    }
+        let mut is_float = first == '.';
+        result.push_str(&self.read_digits(10));
+        if !is_float && self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+            is_float = true;
+            result.push(self.advance().unwrap()); // consume '.'
+            result.push_str(&self.read_digits(10));
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let sign = self.peek_next().filter(|c| *c == '+' || *c == '-');
+            let after_sign = self.src.get(self.pos + 1 + sign.is_some() as usize);
+            if after_sign.is_some_and(|c| c.is_ascii_digit()) {
+                is_float = true;
+                result.push(self.advance().unwrap()); // consume 'e'
+                if sign.is_some() {
                     result.push(self.advance().unwrap());
-                    while let Some(d) = self.peek() {
-                        if d.is_ascii_digit() {
-                            result.push(self.advance().unwrap());
-                        } else {
-                            if d.is_whitespace() || d == ';' {
-                                // If we hit whitespace, we stop reading the number
-                                // if we hit a semicolon, we also stop reading the number
-                                break;
-                            } else {
-                                // If we hit a non-digit, non-whitespace character, it's illegal
-                                return Token::Illegal(d);
-                            }
-                        }
-                    }
-                    return Token::Float(result.parse::<f64>().unwrap());
-                } else {
-                    break;
                 }
+                result.push_str(&self.read_digits(10));
             }
         }
+
+        if let Some(error) = self.number_end() {
+            return error;
+        }
+        if result.ends_with('_') || result.contains("_.") || result.contains("._") {
+            return self.invalid_number("'_' must sit between digits");
+        }
+        let cleaned = result.replace('_', "");
         if is_float {
-            Token::Float(result.parse::<f64>().unwrap())
+            match cleaned.parse::<f64>() {
+                Ok(f) if f.is_finite() => Token::Float(f),
+                _ => self.number_out_of_range("Float"),
+            }
         } else {
-            Token::Number(result.parse::<i64>().unwrap())
+            match cleaned.parse::<i64>() {
+                Ok(n) => Token::Number(n),
+                Err(_) => self.number_out_of_range("Int"),
+            }
         }
     }
 
-    fn read_string(&mut self) -> Token {
+    // Read digits of the given radix, with '_' separators
+    fn read_digits(&mut self, radix: u32) -> String {
+        let mut digits = String::new();
+        while let Some(c) = self.advance_if(|c| c.is_digit(radix) || c == '_') {
+            digits.push(c);
+        }
+        digits
+    }
+
+    // A number runs straight into letters or digits it can't use (`12abc`, `0b102`)
+    fn number_end(&mut self) -> Option<Token> {
+        let mut trailing = String::new();
+        while let Some(c) = self.advance_if(|c| c.is_alphanumeric() || c == '_') {
+            trailing.push(c);
+        }
+        if trailing.is_empty() {
+            return None;
+        }
+        Some(self.invalid_number(&format!("unexpected '{}' after the number", trailing)))
+    }
+
+    fn invalid_number(&mut self, label: &str) -> Token {
+        let error = Diagnostic::error(codes::INVALID_NUMBER, "Invalid number literal", self.token_span())
+            .with_label(label);
+        self.illegal('0', error)
+    }
+
+    fn number_out_of_range(&mut self, kind: &str) -> Token {
+        let error = Diagnostic::error(codes::NUMBER_OUT_OF_RANGE, format!("Number literal is out of range for {}", kind), self.token_span())
+            .with_label("too large")
+            .with_help(match kind {
+                // `-` is a separate operator, so the literal itself is never negative
+                "Int" => "Int literals must be at most 9223372036854775807; write the smallest Int as -9223372036854775807 - 1",
+                _ => "Float literals must be finite 64-bit floating point numbers",
+            });
+        self.illegal('0', error)
+    }
+
+    // Read a string literal; the opening quote has been consumed.
+    // `"""` opens a multi-line string that ends at the next `"""`,
+    // and raw strings (`r"..."`) keep backslashes and `${` as written.
+    fn read_string(&mut self, raw: bool) -> Token {
+        let triple = self.peek() == Some('"') && self.peek_next() == Some('"');
+        if triple {
+            self.advance(); // consume the second and third quotes
+            self.advance();
+        }
         let mut string_val = String::new();
+        let mut parts = Vec::new(); // filled once the string turns out to contain `${...}`
         while let Some(c) = self.peek() {
             if c == '"' {
-                self.advance(); // consume closing quote
-                return Token::String(string_val);
-            } else if c == '\\' {
-                self.advance(); // consume backslash
-                if let Some(escaped_char) = self.advance() {
+                if !triple {
+                    self.advance(); // consume closing quote
+                    return Lexer::string_token(string_val, parts);
+                }
+                if self.peek_next() == Some('"') && self.src.get(self.pos + 2) == Some(&'"') {
+                    for _ in 0..3 {
+                        self.advance(); // consume closing quotes
+                    }
+                    return Lexer::string_token(string_val, parts);
+                }
+                string_val.push(self.advance().unwrap());
+            } else if c == '$' && self.peek_next() == Some('{') && !raw {
+                if !string_val.is_empty() {
+                    parts.push(StrPart::Literal(std::mem::take(&mut string_val)));
+                }
+                match self.read_interpolation() {
+                    Some(part) => parts.push(part),
+                    None => return Token::Illegal('$'),
+                }
+            } else if c == '\\' && !raw {
+                if let Some(escaped_char) = self.read_escape() {
                     string_val.push(escaped_char);
                 }
             } else {
@@ -211,7 +407,159 @@ impl Lexer {
             }
         }
         // If we reach here, it means the string was not properly closed
-        Token::Illegal('"')
+        let closing = if triple { "\"\"\"" } else { "\"" };
+        let error = Diagnostic::error(codes::UNTERMINATED_STRING, "Unterminated string literal", self.token_span())
+            .with_label("string starts here")
+            .with_help(format!("add a closing '{}'", closing));
+        self.illegal('"', error)
+    }
+
+    // A plain string unless interpolations were found
+    fn string_token(rest: String, mut parts: Vec<StrPart>) -> Token {
+        if parts.is_empty() {
+            return Token::String(rest);
+        }
+        if !rest.is_empty() {
+            parts.push(StrPart::Literal(rest));
+        }
+        Token::Interpolated(parts)
+    }
+
+    // Read the source of a `${...}` interpolation, starting at the `$`.
+    // Braces nest, and braces inside nested string literals don't count.
+    fn read_interpolation(&mut self) -> Option<StrPart> {
+        let open = self.mark();
+        self.advance(); // consume '$'
+        self.advance(); // consume '{'
+        let start = self.mark();
+        let mut code = String::new();
+        let mut depth = 0;
+        let mut in_string = false;
+        while let Some(c) = self.peek() {
+            if !in_string && c == '}' && depth == 0 {
+                self.advance(); // consume '}'
+                return Some(StrPart::Code { source: code, start });
+            }
+            match c {
+                '"' => in_string = !in_string,
+                '\\' if in_string => {
+                    code.push(self.advance().unwrap()); // keep the escaped char with its backslash
+                }
+                '{' if !in_string => depth += 1,
+                '}' if !in_string => depth -= 1,
+                _ => {}
+            }
+            if let Some(c) = self.advance() {
+                code.push(c);
+            }
+        }
+        let error = Diagnostic::error(codes::UNTERMINATED_INTERPOLATION, "Unterminated interpolation", self.span_since(open))
+            .with_label("interpolation starts here")
+            .with_help("close it with '}'");
+        self.errors.push(error);
+        None
+    }
+
+    // Read a character literal; the opening quote has been consumed.
+    // It takes the same escapes as strings.
+    fn read_char(&mut self) -> Token {
+        let value = match self.peek() {
+            Some('\\') => self.read_escape(),
+            Some('\'') => {
+                self.advance(); // consume closing quote
+                let error = Diagnostic::error(codes::INVALID_CHAR, "Empty character literal", self.token_span())
+                    .with_label("expected one character");
+                return self.illegal('\'', error);
+            }
+            Some(c) if c != '\n' => self.advance(),
+            _ => None,
+        };
+        if self.advance_if(|c| c == '\'').is_some() {
+            return match value {
+                Some(c) => Token::Char(c),
+                None => Token::Illegal('\\'), // invalid escape, already reported
+            };
+        }
+
+        // look for the closing quote on this line to tell a too-long literal from an unclosed one
+        let rest = self.src[self.pos..].iter().take_while(|c| **c != '\n');
+        if let Some(len) = rest.clone().position(|c| *c == '\'') {
+            for _ in 0..=len {
+                self.advance();
+            }
+            let error = Diagnostic::error(codes::INVALID_CHAR, "Character literal may only contain one character", self.token_span())
+                .with_label("more than one character")
+                .with_help("use double quotes for a string");
+            return self.illegal('\'', error);
+        }
+        let error = Diagnostic::error(codes::INVALID_CHAR, "Unterminated character literal", self.token_span())
+            .with_label("character starts here")
+            .with_help("add a closing \"'\"");
+        self.illegal('\'', error)
+    }
+
+    // Read an escape sequence starting at the backslash.
+    // Invalid escapes are reported and produce no character, so lexing carries on.
+    fn read_escape(&mut self) -> Option<char> {
+        let (start, start_pos) = (self.mark(), self.pos);
+        self.advance(); // consume backslash
+        let escaped = match self.advance() {
+            Some('n') => Some('\n'),
+            Some('t') => Some('\t'),
+            Some('r') => Some('\r'),
+            Some('0') => Some('\0'),
+            Some('\\') => Some('\\'),
+            Some('"') => Some('"'),
+            Some('\'') => Some('\''),
+            Some('$') => Some('$'),
+            Some('x') => {
+                let digits: String = (0..2).filter_map(|_| self.advance_if(|d| d.is_ascii_hexdigit())).collect();
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|n| digits.len() == 2 && *n <= 0x7F)
+                    .and_then(char::from_u32)
+            }
+            Some('u') => {
+                let mut digits = String::new();
+                if self.advance_if(|d| d == '{').is_some() {
+                    while let Some(d) = self.advance_if(|d| d.is_ascii_hexdigit()) {
+                        digits.push(d);
+                    }
+                    if self.advance_if(|d| d == '}').is_none() {
+                        digits.clear();
+                    }
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| digits.len() <= 6)
+                    .and_then(char::from_u32)
+            }
+            _ => None,
+        };
+        if escaped.is_none() {
+            let text: String = self.src[start_pos..self.pos].iter().collect();
+            let error = Diagnostic::error(codes::INVALID_ESCAPE, format!("Invalid escape sequence '{}'", text), self.span_since(start))
+                .with_label("not a valid escape")
+                .with_help("valid escapes are \\n \\t \\r \\0 \\\\ \\\" \\' \\$ \\xNN (up to 7F) and \\u{NNNN}");
+            self.errors.push(error);
+        }
+        escaped
+    }
+
+    /// An operator, or its compound assignment form when it is followed by `=`
+    fn compound(&mut self, op: Token, assign: Token) -> Token {
+        match self.advance_if(|c| c == '=') {
+            Some(_) => assign,
+            None => op,
+        }
+    }
+
+    // Consume the next character if it satisfies `pred`
+    fn advance_if(&mut self, pred: impl Fn(char) -> bool) -> Option<char> {
+        match self.peek() {
+            Some(c) if pred(c) => self.advance(),
+            _ => None,
+        }
     }
 
     fn read_identifier(&mut self) -> Token {
@@ -227,20 +575,143 @@ impl Lexer {
         }
         match identifier.as_str() {
             "var" => Token::Var,
+            "const" => Token::Const,
             "true" => Token::Bool(true),
             "false" => Token::Bool(false),
             "print" => Token::Print,
             "if" => Token::If,
             "else" => Token::Else,
             "elif" => Token::Elif,
+            "while" => Token::While,
             "for" => Token::For,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
             "in" => Token::In,
+            "is" => Token::Is,
             "range" => Token::Range,
             "fn" => Token::Fn,
+            "class" => Token::Class,
+            "super" => Token::Super,
             "return" => Token::Return,
+            "not" => Token::Not,
+            "and" => Token::And,
+            "or" => Token::Or,
             _ => Token::Identifier {
                 name: identifier,
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lex `src` up to end of file, failing if the lexer reports any error
+    fn tokens(src: &str) -> Vec<Token> {
+        let mut lexer = Lexer::new(src, "<test>");
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token().token;
+            if token == Token::EOF {
+                break;
+            }
+            tokens.push(token);
+        }
+        let errors = lexer.take_errors();
+        assert!(errors.is_empty(), "errors lexing {src:?}: {errors:?}");
+        tokens
+    }
+
+    /// Lex the single literal `src`
+    fn token(src: &str) -> Token {
+        let mut tokens = tokens(src);
+        assert_eq!(tokens.len(), 1, "expected one token in {src:?}: {tokens:?}");
+        tokens.remove(0)
+    }
+
+    /// The codes of the errors reported while lexing all of `src`
+    fn error_codes(src: &str) -> Vec<&'static str> {
+        let mut lexer = Lexer::new(src, "<test>");
+        while lexer.next_token().token != Token::EOF {}
+        lexer.take_errors().iter().map(|e| e.code).collect()
+    }
+
+    fn string(s: &str) -> Token {
+        Token::String(s.to_string())
+    }
+
+    #[test]
+    fn escapes_in_strings() {
+        assert_eq!(token(r#""a\nb\tc\r\0""#), string("a\nb\tc\r\0"));
+        assert_eq!(token(r#""\\ \" \' \$""#), string("\\ \" ' $"));
+        assert_eq!(token(r#""\x41\x7F""#), string("A\x7F"));
+        assert_eq!(token(r#""\u{1F600}\u{e9}\u{0}""#), string("\u{1F600}é\0"));
+        assert_eq!(token(r#"'\n'"#), Token::Char('\n'));
+        assert_eq!(token(r#"'\u{263A}'"#), Token::Char('☺'));
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        for src in [
+            r#""\q""#,
+            r#""\x80""#,
+            r#""\x4""#,
+            r#""\u{}""#,
+            r#""\u{1234567}""#,
+            r#""\u{D800}""#,
+            r#""\u41""#,
+            r#""\u{41""#,
+        ] {
+            assert_eq!(error_codes(src), [codes::INVALID_ESCAPE], "{src}");
+        }
+    }
+
+    #[test]
+    fn raw_and_triple_quoted_strings() {
+        assert_eq!(token(r#"r"C:\path\n${x}""#), string(r"C:\path\n${x}"));
+        assert_eq!(token("\"\"\"one\n\"two\"\n\"\"\""), string("one\n\"two\"\n"));
+        assert_eq!(token(r#""""a\tb""""#), string("a\tb"));
+        assert_eq!(error_codes("\"open"), [codes::UNTERMINATED_STRING]);
+        assert_eq!(error_codes("\"\"\"open\"\""), [codes::UNTERMINATED_STRING]);
+    }
+
+    #[test]
+    fn invalid_characters() {
+        assert_eq!(token("'a'"), Token::Char('a'));
+        assert_eq!(token("'é'"), Token::Char('é'));
+        assert_eq!(error_codes("''"), [codes::INVALID_CHAR]);
+        assert_eq!(error_codes("'ab'"), [codes::INVALID_CHAR]);
+        assert_eq!(error_codes("'a"), [codes::INVALID_CHAR]);
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(token("42"), Token::Number(42));
+        assert_eq!(token("1_000_000"), Token::Number(1_000_000));
+        assert_eq!(token("0xFF"), Token::Number(255));
+        assert_eq!(token("0b1010"), Token::Number(10));
+        assert_eq!(token("0o17"), Token::Number(15));
+        assert_eq!(token("0xdead_beef"), Token::Number(0xdead_beef));
+        assert_eq!(token("9223372036854775807"), Token::Number(i64::MAX));
+        assert_eq!(token("1.5"), Token::Float(1.5));
+        assert_eq!(token(".5"), Token::Float(0.5));
+        assert_eq!(token("1.5e-3"), Token::Float(1.5e-3));
+        assert_eq!(token("2E+2"), Token::Float(200.0));
+        assert_eq!(token("1_000.000_1"), Token::Float(1000.0001));
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        for src in ["1e", "1e+", "1_", "1_.5", "0x", "0b_", "12abc", "0b102", "0xFG"] {
+            assert_eq!(error_codes(src), [codes::INVALID_NUMBER], "{src}");
+        }
+    }
+
+    #[test]
+    fn numbers_out_of_range() {
+        for src in ["9223372036854775808", "0xFFFF_FFFF_FFFF_FFFF", "1e400", "1.0e309"] {
+            assert_eq!(error_codes(src), [codes::NUMBER_OUT_OF_RANGE], "{src}");
+        }
+    }
+}
@@ -1,3 +1,9 @@
+// Errors are only built on the failure path and carry a full diagnostic; boxing them buys nothing.
+#![allow(clippy::result_large_err)]
+
+mod builtins;
+mod codes;
+mod diagnostics;
 mod evalulator;
 mod enviorment;
 mod helper;
@@ -10,25 +16,53 @@ use std::{env, fs, io, path::{Path, PathBuf}};
 use lexer::Lexer;
 use parser::Parser;
 use evalulator::eval_program;
+use diagnostics::ErrorFormat;
+
+/// Stack for the thread that runs Fig code; deep but bounded recursion (see `evalulator::MAX_CALL_DEPTH`)
+/// needs far more than the default main thread gets, especially in debug builds
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
-    // CLI: fig [run [path]] | new <name>
+    let cli = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_cli)
+        .expect("failed to start the interpreter thread");
+    if cli.join().is_err() {
+        std::process::exit(101); // the panic message has already been printed
+    }
+}
+
+fn run_cli() {
+    // CLI: fig [run [--error-format=human|json] [path]] | new <name> | explain <code>
     let mut args = env::args().skip(1);
     match args.next().as_deref() {
         None => {
             // cargo run (no args) -> run ./src/main.fg or ./main.fg
-            run_entry_default().unwrap_or_else(|e| {
-                eprintln!("error: {e}");
+            if let Err(e) = run_entry_default(ErrorFormat::Human) {
+                report_failure(e, ".", ErrorFormat::Human);
                 std::process::exit(1);
-            });
+            }
         }
         Some("run") => {
-            // fig run [path] (path can be a directory or a file)
-            let target = args.next().unwrap_or_else(|| ".".to_string());
-            run_target(&target).unwrap_or_else(|e| {
-                eprintln!("error: {e}");
+            // fig run [--error-format=json] [path] (path can be a directory or a file)
+            let mut format = ErrorFormat::Human;
+            let mut target = None;
+            while let Some(arg) = args.next() {
+                let value = if arg == "--error-format" {
+                    args.next()
+                } else if let Some(value) = arg.strip_prefix("--error-format=") {
+                    Some(value.to_string())
+                } else {
+                    target = Some(arg);
+                    continue;
+                };
+                format = parse_error_format(value.as_deref());
+            }
+            let target = target.unwrap_or_else(|| ".".to_string());
+            if let Err(e) = run_target(&target, format) {
+                report_failure(e, &target, format);
                 std::process::exit(1);
-            });
+            }
         }
         Some("new") => {
             // fig new <name>
@@ -42,21 +76,81 @@ fn main() {
             });
             println!("Created {name}/ with src/main.fg");
         }
+        Some("explain") => {
+            // fig explain <code>
+            let code = args.next().unwrap_or_else(|| {
+                eprintln!("usage: fig explain <code>");
+                std::process::exit(2);
+            });
+            match codes::explain(&code) {
+                Some(explanation) => print_explanation(explanation),
+                None => {
+                    eprintln!("error: no explanation for code {code}");
+                    std::process::exit(1);
+                }
+            }
+        }
         Some(cmd) => {
-            eprintln!("unknown command: {cmd}\nusage:\n  fig run [path]\n  fig new <name>");
+            eprintln!("unknown command: {cmd}\nusage:\n  fig run [--error-format=human|json] [path]\n  fig new <name>\n  fig explain <code>");
             std::process::exit(2);
         }
     }
 }
 
-fn run_entry_default() -> io::Result<()> {
+fn print_explanation(explanation: &codes::Explanation) {
+    let indent = |code: &str| {
+        code.lines().map(|line| format!("    {line}\n")).collect::<String>()
+    };
+    println!("{}: {}\n", explanation.code, explanation.title);
+    println!("{}\n", explanation.description);
+    println!("Erroneous example:\n\n{}", indent(explanation.wrong));
+    print!("Corrected:\n\n{}", indent(explanation.fixed));
+}
+
+fn parse_error_format(value: Option<&str>) -> ErrorFormat {
+    match value {
+        Some("human") => ErrorFormat::Human,
+        Some("json") => ErrorFormat::Json,
+        _ => {
+            eprintln!("usage: --error-format=human|json");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Why a program could not be run
+enum RunError {
+    Io(io::Error), // the entry point could not be found or read
+    Reported { code: &'static str, summary: String }, // its diagnostics have already been emitted
+}
+
+impl From<io::Error> for RunError {
+    fn from(e: io::Error) -> Self {
+        RunError::Io(e)
+    }
+}
+
+/// Print what is left to say about a failed run of `target`.
+/// JSON consumers only expect diagnostic objects on stderr, so reported errors get no summary line there.
+fn report_failure(error: RunError, target: &str, format: ErrorFormat) {
+    match error {
+        RunError::Io(e) => diagnostics::emit_failure(&e.to_string(), target, format),
+        RunError::Reported { .. } if format == ErrorFormat::Json => {}
+        RunError::Reported { code, summary } => {
+            eprintln!("For more information about this error, try `fig explain {code}`.");
+            diagnostics::emit_failure(&summary, target, format);
+        }
+    }
+}
+
+fn run_entry_default(format: ErrorFormat) -> Result<(), RunError> {
     // Prefer ./src/main.fg, fallback to ./main.fg
     let entry = find_entry(Path::new("."))?
         .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no src/main.fg or main.fg found"))?;
-    run_file(&entry)
+    run_file(&entry, format)
 }
 
-fn run_target(target: &str) -> io::Result<()> {
+fn run_target(target: &str, format: ErrorFormat) -> Result<(), RunError> {
     let p = Path::new(target);
     let entry = if p.is_dir() {
         find_entry(p)?
@@ -64,7 +158,7 @@ fn run_target(target: &str) -> io::Result<()> {
     } else {
         p.to_path_buf()
     };
-    run_file(&entry)
+    run_file(&entry, format)
 }
 
 fn find_entry(root: &Path) -> io::Result<Option<PathBuf>> {
@@ -79,13 +173,31 @@ fn find_entry(root: &Path) -> io::Result<Option<PathBuf>> {
     Ok(None)
 }
 
-fn run_file(path: &Path) -> io::Result<()> {
+fn run_file(path: &Path, format: ErrorFormat) -> Result<(), RunError> {
     let source = fs::read_to_string(path)?;
-    let lexer = Lexer::new(&source);
+    let lexer = Lexer::new(&source, &path.display().to_string());
     let mut parser = Parser::new(lexer);
-    let program = parser.parse();
+    let program = match parser.parse() {
+        Ok(program) => program,
+        Err(errors) => {
+            for e in &errors {
+                diagnostics::emit(e, &source, format);
+            }
+            let plural = if errors.len() == 1 { "" } else { "s" };
+            return Err(RunError::Reported {
+                code: errors[0].code,
+                summary: format!("could not run {} due to {} syntax error{plural}", path.display(), errors.len()),
+            });
+        }
+    };
     // Run program; printing is handled by the 'print' builtin
-    let _ = eval_program(&program);
+    if let Err(e) = eval_program(&program) {
+        diagnostics::emit(&e.to_diagnostic(), &source, format);
+        return Err(RunError::Reported {
+            code: e.code,
+            summary: format!("could not run {} due to a runtime error", path.display()),
+        });
+    }
     Ok(())
 }
 
@@ -1,31 +1,64 @@
+use crate::codes;
+use crate::diagnostics::{did_you_mean, Diagnostic};
 use crate::lexer::Lexer;
-use crate::token::Token;
-use crate::helper::{Expr, Stmt, Precedence, precedence};
+use crate::token::{Span, StrPart, Token};
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::helper::{associativity, compound_operator, precedence, Associativity, Expr, ExprKind, Field, Precedence, Stmt, StmtKind};
 
 pub struct Parser {
     lexer: Lexer,
     current: Token,
     next: Token,
+    current_span: Span,
+    next_span: Span,
+    prev_span: Span, // span of the last consumed token
+    errors: Vec<ParseError>,
+    loops: Vec<Option<String>>, // labels of the loops around the current statement, innermost last
+    scopes: Vec<HashMap<String, Option<Span>>>, // names declared in each enclosing scope, innermost last; constants keep their declaration
+    classes: Vec<Option<bool>>, // for each class body around the current statement, whether that class has a parent; `None` inside a field default
 }
 
 impl Parser {
     pub fn new(mut lexer: Lexer) -> Self {
         let current = lexer.next_token();
         let next    = lexer.next_token();
-        Parser { lexer, current, next }
+        Parser {
+            lexer,
+            current: current.token,
+            next: next.token,
+            prev_span: current.span.clone(),
+            current_span: current.span,
+            next_span: next.span,
+            errors: Vec::new(),
+            loops: Vec::new(),
+            scopes: vec![HashMap::new()],
+            classes: Vec::new(),
+        }
     }
 
     fn advance(&mut self) {
-        self.current = std::mem::replace(&mut self.next, self.lexer.next_token());
+        let upcoming = self.lexer.next_token();
+        self.current = std::mem::replace(&mut self.next, upcoming.token);
+        let span = std::mem::replace(&mut self.next_span, upcoming.span);
+        self.prev_span = std::mem::replace(&mut self.current_span, span);
+    }
+
+    /// Span from `start` up to the end of the last consumed token
+    fn span_from(&self, start: &Span) -> Span {
+        start.to(&self.prev_span)
     }
 
 
     /// Parses a single statement (either `var x = …;` or an expression-stmt like `x + 2;`)
-    pub fn parse_stmt(&mut self) -> Stmt {
-        match self.current.clone() {
-            Token::Var => {
-                // var-declaration
-                self.advance(); // consume 'var'
+    pub fn parse_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.current_span.clone();
+        let stmt = match self.current.clone() {
+            Token::Var | Token::Const => {
+                // var or const declaration
+                let constant = self.current == Token::Const;
+                let keyword = self.current.clone();
+                self.advance(); // consume 'var' or 'const'
                 // println!("Should be identifier now: {:?}", self.current);
 
 
@@ -33,28 +66,31 @@ impl Parser {
                 let name = if let Token::Identifier { name } = self.current.clone() {
                     name
                 } else {
-                    panic!("Expected identifier after 'var', got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_IDENTIFIER, &format!("Expected identifier after {}", keyword)));
                 };
+                let name_span = self.current_span.clone();
                 self.advance(); // consume the identifier
                 // println!("Should be = now: {:?}", self.current);
 
                 // expect '='
                 if self.current != Token::Equal {
-                    panic!("Expected '=' after variable name, got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected '=' after variable name"));
                 }
                 self.advance(); // consume '='
 
                 // parse the initializer expression
-                let value = self.parse_expression(Precedence::Lowest);
+                let value = self.parse_expression(Precedence::Lowest)?;
                 // println!("The value {:?}", value);
 
                 // expect semicolon
                 if self.current != Token::Semicolon {
-                    panic!("Expected ';' after var declaration, got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_SEMICOLON, &format!("Expected ';' after {} declaration", keyword)));
                 }
                 self.advance(); // consume ';'
 
-                Stmt::VarDecl { name, value }
+                // declared after the initializer, which still sees any outer variable of the same name
+                self.declare(&name, &name_span, constant);
+                Stmt::new(StmtKind::VarDecl { name, value, constant }, self.span_from(&start))
             }
 
             Token::Print => {
@@ -62,68 +98,69 @@ impl Parser {
                 self.advance(); // consume 'print'
 
                 // parse the expression to print
-                let expr = self.parse_expression(Precedence::Lowest);
+                let expr = self.parse_expression(Precedence::Lowest)?;
 
                 // expect semicolon
 
                 if self.current != Token::Semicolon {
-                    panic!("Expected ';' after print expression, got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_SEMICOLON, "Expected ';' after print expression"));
                 }
                 self.advance(); // consume ';'
 
-                Stmt::PrntStmt(expr)
+                Stmt::new(StmtKind::PrntStmt(expr), self.span_from(&start))
             }
 
-            Token::Fn => {
-                self.advance(); // consume 'fn'
+            Token::Fn if self.next != Token::LParen => {
+                let name_span = self.next_span.clone();
+                let function = self.parse_function(start)?;
+                if let StmtKind::Function { name, .. } = &function.kind {
+                    self.declare(name, &name_span, false);
+                }
+                function
+            }
+
+            Token::Class => {
+                self.advance(); // consume 'class'
 
                 let name = if let Token::Identifier { name } = self.current.clone() {
                     name
                 } else {
-                    panic!("Expected function name after 'fn', got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_IDENTIFIER, "Expected class name after 'class'"));
                 };
+                let name_span = self.current_span.clone();
                 self.advance(); // consume identifier
 
-                if self.current != Token::LParen {
-                    panic!("Expected '(' after function name, got {:?}", self.current);
-                }
-                self.advance(); // consume '('
-
-                let mut params = Vec::new();
-                if self.current != Token::RParen {
-                    loop {
-                        if let Token::Identifier { name } = self.current.clone() {
-                            params.push(name);
-                        } else {
-                            panic!("Expected parameter name, got {:?}", self.current);
-                        }
-                        self.advance(); // consume parameter
-
-                        if self.current == Token::Comma {
-                            self.advance(); // consume ','
-                            continue;
-                        }
-                        break;
+                // `class Person() { ... }` or `class Manager(Employee) { ... }`; the parentheses are optional
+                let mut parent = None;
+                if self.current == Token::LParen {
+                    self.advance(); // consume '('
+                    if let Token::Identifier { name } = self.current.clone() {
+                        parent = Some(Expr::new(ExprKind::Var(name), self.current_span.clone()));
+                        self.advance(); // consume the parent's name
                     }
+                    if self.current != Token::RParen {
+                        return Err(self.error(codes::EXPECTED_TOKEN, "Expected ')' after parent class"));
+                    }
+                    self.advance(); // consume ')'
                 }
 
-                if self.current != Token::RParen {
-                    panic!("Expected ')' after parameters, got {:?}", self.current);
-                }
-                self.advance(); // consume ')'
-
                 if self.current != Token::LBrace {
-                    panic!("Expected '{{' to start function body, got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected '{' to start class body"));
                 }
                 self.advance(); // consume '{'
 
-                let body = self.parse_block();
+                self.classes.push(Some(parent.is_some()));
+                let body = self.parse_class_body();
+                self.classes.pop();
+                let (fields, methods) = body?;
 
-                Stmt::Function {
-                    name,
-                    params,
-                    body: Box::new(body),
+                if self.current != Token::RBrace {
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected '}' at end of class body"));
                 }
+                self.advance(); // consume '}'
+
+                self.declare(&name, &name_span, false);
+                Stmt::new(StmtKind::Class { name, parent, fields, methods }, self.span_from(&start))
             }
 
             Token::Return => {
@@ -131,228 +168,648 @@ impl Parser {
 
                 if self.current == Token::Semicolon {
                     self.advance(); // consume ';'
-                    Stmt::Return(None)
+                    Stmt::new(StmtKind::Return(None), self.span_from(&start))
                 } else {
-                    let value = self.parse_expression(Precedence::Lowest);
+                    let value = self.parse_expression(Precedence::Lowest)?;
                     if self.current != Token::Semicolon {
-                        panic!("Expected ';' after return value, got {:?}", self.current);
+                        return Err(self.error(codes::EXPECTED_SEMICOLON, "Expected ';' after return value"));
                     }
                     self.advance(); // consume ';'
-                    Stmt::Return(Some(value))
+                    Stmt::new(StmtKind::Return(Some(value)), self.span_from(&start))
                 }
             }
 
-            Token::Identifier { .. } => {
-                if self.next == Token::Equal {
-                    let name = if let Token::Identifier { name } = self.current.clone() {
-                        name
-                    } else {
-                        unreachable!();
-                    };
-                    self.advance(); // consume identifier
-                    self.advance(); // consume '='
-                    let value = self.parse_expression(Precedence::Lowest);
-                    if self.current != Token::Semicolon {
-                        panic!("Expected ';' after assignment, got {:?}", self.current);
-                    }
-                    self.advance(); // consume ';'
-                    Stmt::VarDecl { name, value }
-                } else {
-                    let expr = self.parse_expression(Precedence::Lowest);
-                    if self.current != Token::Semicolon {
-                        panic!("Expected ';' after expression, got {:?}", self.current);
-                    }
-                    self.advance(); // consume ';'
-                    Stmt::ExprStmt(expr)
+            Token::Identifier { name } if self.next == Token::Colon => {
+                self.advance(); // consume the label
+                self.advance(); // consume ':'
+                if !matches!(self.current, Token::For | Token::While) {
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected 'for' or 'while' after loop label"));
                 }
+                self.parse_loop(Some(name), start)?
             }
 
-            Token::For => {
-                self.advance(); // consume 'for'
+            Token::Break | Token::Continue => {
+                let keyword = self.current.clone();
+                self.advance(); // consume 'break' or 'continue'
 
-                // expect identifier
-                let var_name = if let Token::Identifier { name } = self.current.clone() {
-                    name
+                let label = if let Token::Identifier { name } = self.current.clone() {
+                    self.advance(); // consume the label
+                    Some(name)
                 } else {
-                    panic!("Expected identifier after 'for', got {:?}", self.current);
+                    None
                 };
-                self.advance(); // consume identifier
-
-                // expect 'in'
-                if !matches!(self.current, Token::In) {
-                    panic!("Expected 'in' after variable name in for loop, got {:?}", self.current);
-                }
-                self.advance(); // consume 'in'
 
-                // Handle range expression directly
-                let range = self.parse_expression(Precedence::Lowest);
-                // expect '{'
-                if !matches!(self.current, Token::LBrace) {
-                    panic!("Expected '{{' after for loop header, got {:?}", self.current);
+                if self.current != Token::Semicolon {
+                    return Err(self.error(codes::EXPECTED_SEMICOLON, &format!("Expected ';' after {}", keyword)));
                 }
-                self.advance(); // consume '{'
-
-                let body = Box::new(self.parse_block());
+                self.advance(); // consume ';'
 
-                Stmt::ForStmt {
-                    var_name,
-                    range,
-                    body,
-                }
+                let span = self.span_from(&start);
+                self.check_loop_target(&keyword, label.as_deref(), &span);
+                let kind = if keyword == Token::Break {
+                    StmtKind::Break(label)
+                } else {
+                    StmtKind::Continue(label)
+                };
+                Stmt::new(kind, span)
             }
 
+            Token::For | Token::While => self.parse_loop(None, start)?,
+
             Token::If | Token::Elif => {
                 self.advance(); // consume 'if' or 'elif'
 
                 // parse the condition expression
-                let condition = self.parse_expression(Precedence::Lowest);
+                let condition = self.parse_expression(Precedence::Lowest)?;
 
                 // expect '{' for then branch
                 if self.current != Token::LBrace {
-                    panic!("Expected '{{' after condition, got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected '{' after condition"));
                 }
                 self.advance(); // consume '{'
 
                 // parse the then branch (multiple statements)
-                let then_block = self.parse_block();
+                let then_block = self.parse_block()?;
                 
                 // check for else or elif branch
                 let else_branch = if self.current == Token::Else {
                     self.advance(); // consume 'else'
                     if self.current != Token::LBrace {
-                        panic!("Expected '{{' after 'else', got {:?}", self.current);
+                        return Err(self.error(codes::EXPECTED_TOKEN, "Expected '{' after 'else'"));
                     }
                     self.advance(); // consume '{'
-                    let block = self.parse_block();
+                    let block = self.parse_block()?;
                     Some(Box::new(block))
                 } else if self.current == Token::Elif {
-                    Some(Box::new(self.parse_stmt()))
+                    Some(Box::new(self.parse_stmt()?))
                 } else {
                     None
                 };
 
-                Stmt::IfStmt {
-                    condition,
-                    then_branch: Box::new(then_block),
-                    else_branch,
-                }
+                Stmt::new(
+                    StmtKind::IfStmt {
+                        condition,
+                        then_branch: Box::new(then_block),
+                        else_branch,
+                    },
+                    self.span_from(&start),
+                )
             }
 
             _ => {
                 // expression statement
-                let expr = self.parse_expression(Precedence::Lowest);
+                let expr = self.parse_expression(Precedence::Lowest)?;
 
                 // expect semicolon
                 if self.current != Token::Semicolon {
-                    panic!("Expected ';' after expression, got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_SEMICOLON, "Expected ';' after expression"));
                 }
                 self.advance(); // consume ';'
-                Stmt::ExprStmt(expr)
+                Stmt::new(StmtKind::ExprStmt(expr), self.span_from(&start))
             }
+        };
+        Ok(stmt)
+    }
+
+    /// Parse `fn name(params) { body }`, a function or a method
+    fn parse_function(&mut self, start: Span) -> Result<Stmt, ParseError> {
+        self.advance(); // consume 'fn'
+
+        let name = if let Token::Identifier { name } = self.current.clone() {
+            name
+        } else {
+            return Err(self.error(codes::EXPECTED_IDENTIFIER, "Expected function name after 'fn'"));
+        };
+        self.advance(); // consume identifier
+
+        let params = self.parse_params()?;
+        let body = self.parse_function_body(&params)?;
+
+        Ok(Stmt::new(StmtKind::Function { name, params, body }, self.span_from(&start)))
+    }
+
+    /// Parse `(a, b, ...)`, the parameters of a function
+    fn parse_params(&mut self) -> Result<Vec<String>, ParseError> {
+        if self.current != Token::LParen {
+            return Err(self.error(codes::EXPECTED_TOKEN, "Expected '(' before parameters"));
         }
+        self.advance(); // consume '('
+
+        let mut params = Vec::new();
+        if self.current != Token::RParen {
+            loop {
+                if let Token::Identifier { name } = self.current.clone() {
+                    params.push(name);
+                } else {
+                    return Err(self.error(codes::EXPECTED_IDENTIFIER, "Expected parameter name"));
+                }
+                self.advance(); // consume parameter
+
+                if self.current == Token::Comma {
+                    self.advance(); // consume ','
+                    continue;
+                }
+                break;
+            }
+        }
+
+        if self.current != Token::RParen {
+            return Err(self.error(codes::EXPECTED_TOKEN, "Expected ')' after parameters"));
+        }
+        self.advance(); // consume ')'
+        Ok(params)
+    }
+
+    /// Parse `{ body }` of a function taking `params`
+    fn parse_function_body(&mut self, params: &[String]) -> Result<Rc<Stmt>, ParseError> {
+        if self.current != Token::LBrace {
+            return Err(self.error(codes::EXPECTED_TOKEN, "Expected '{' to start function body"));
+        }
+        self.advance(); // consume '{'
+        self.in_function(params, |parser| parser.parse_block()).map(Rc::new)
+    }
+
+    /// Run `parse` for the body of a function taking `params`
+    fn in_function<T>(&mut self, params: &[String], parse: impl FnOnce(&mut Self) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        // loops outside the function cannot be reached with break or continue
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        self.scopes.push(params.iter().map(|p| (p.clone(), None)).collect());
+        let body = parse(self);
+        self.scopes.pop();
+        self.loops = enclosing_loops;
+        body
+    }
+
+    /// Parse a `for` or `while` loop, optionally labelled (`outer: for ...`)
+    fn parse_loop(&mut self, label: Option<String>, start: Span) -> Result<Stmt, ParseError> {
+        let kind = if self.current == Token::For {
+            self.advance(); // consume 'for'
+
+            // expect identifier
+            let var_name = if let Token::Identifier { name } = self.current.clone() {
+                name
+            } else {
+                return Err(self.error(codes::EXPECTED_IDENTIFIER, "Expected identifier after 'for'"));
+            };
+            self.advance(); // consume identifier
+
+            // expect 'in'
+            if !matches!(self.current, Token::In) {
+                return Err(self.error(codes::EXPECTED_TOKEN, "Expected 'in' after variable name in for loop"));
+            }
+            self.advance(); // consume 'in'
+
+            // Handle range expression directly
+            let range = self.parse_expression(Precedence::Lowest)?;
+            // expect '{'
+            if !matches!(self.current, Token::LBrace) {
+                return Err(self.error(codes::EXPECTED_TOKEN, "Expected '{' after for loop header"));
+            }
+            self.advance(); // consume '{'
+
+            self.scopes.push(HashMap::from([(var_name.clone(), None)]));
+            let body = self.parse_loop_body(&label);
+            self.scopes.pop();
+            let body = Box::new(body?);
+            StmtKind::ForStmt { label, var_name, range, body }
+        } else {
+            self.advance(); // consume 'while'
+
+            let condition = self.parse_expression(Precedence::Lowest)?;
+
+            if self.current != Token::LBrace {
+                return Err(self.error(codes::EXPECTED_TOKEN, "Expected '{' after while condition"));
+            }
+            self.advance(); // consume '{'
+
+            let body = Box::new(self.parse_loop_body(&label)?);
+            StmtKind::While { label, condition, body }
+        };
+        Ok(Stmt::new(kind, self.span_from(&start)))
+    }
+
+    /// Parse a loop body, where `break` and `continue` are allowed
+    fn parse_loop_body(&mut self, label: &Option<String>) -> Result<Stmt, ParseError> {
+        self.loops.push(label.clone());
+        let body = self.parse_block();
+        self.loops.pop();
+        body
+    }
+
+    /// Record a declaration of `name` at `span` in the innermost scope, `constant` for a `const`
+    fn declare(&mut self, name: &str, span: &Span, constant: bool) {
+        // a constant cannot be replaced in its own scope, only shadowed in an inner one
+        if let Some(Some(declared)) = self.scopes.last().and_then(|scope| scope.get(name)).cloned() {
+            self.errors.push(
+                Diagnostic::error(codes::ASSIGN_TO_CONSTANT, format!("Cannot redeclare constant: {}", name), span.clone())
+                    .with_label("already declared as a constant in this scope")
+                    .with_note(format!("`{}` is declared with `const` at {}", name, declared))
+                    .with_help("use a different name, or shadow it inside a block"),
+            );
+            return;
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), constant.then(|| span.clone()));
+        }
+    }
+
+    /// Where `name` was declared as a constant, if the closest declaration the parser has seen is a `const`.
+    /// Names declared later (such as globals used inside a function) are left to the evaluator.
+    fn constant_declaration(&self, name: &str) -> Option<Span> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned().flatten()
+    }
+
+    /// Report a `break` or `continue` that has no loop to act on
+    fn check_loop_target(&mut self, keyword: &Token, label: Option<&str>, span: &Span) {
+        if self.loops.is_empty() {
+            self.errors.push(
+                Diagnostic::error(codes::BREAK_OUTSIDE_LOOP, format!("{} outside of a loop", keyword), span.clone())
+                    .with_label(format!("cannot {} outside of a loop", keyword)),
+            );
+            return;
+        }
+        let Some(label) = label else { return };
+        if self.loops.iter().flatten().any(|l| l == label) {
+            return;
+        }
+        let error = Diagnostic::error(codes::UNDEFINED_LABEL, format!("Undefined loop label: {}", label), span.clone())
+            .with_label("no enclosing loop has this label");
+        let error = match did_you_mean(label, self.loops.iter().flatten().map(String::as_str)) {
+            Some(suggestion) => error.with_help(format!("did you mean `{}`?", suggestion)),
+            None => error,
+        };
+        self.errors.push(error);
     }
 
     /// Parses an expression with precedence climbing
-    pub fn parse_expression(&mut self, prec: Precedence) -> Expr {
+    pub fn parse_expression(&mut self, prec: Precedence) -> Result<Expr, ParseError> {
+        let start = self.current_span.clone();
         let mut left = match &self.current {
             
             Token::Number(n) => {
-                let expr = Expr::Number(*n);
+                let expr = Expr::new(ExprKind::Number(*n), start.clone());
                 self.advance();
                 expr
             }
             Token::Float(f) => {
-                let expr = Expr::Float(*f);
+                let expr = Expr::new(ExprKind::Float(*f), start.clone());
                 self.advance();
                 expr
             }
             Token::String(s) => {
-                let expr = Expr::String(s.clone());
+                let expr = Expr::new(ExprKind::String(s.clone()), start.clone());
                 self.advance();
                 expr
             }
+            Token::Char(c) => {
+                let expr = Expr::new(ExprKind::Char(*c), start.clone());
+                self.advance();
+                expr
+            }
+            Token::Interpolated(parts) => {
+                let parts = parts.clone();
+                self.advance();
+                let mut exprs = Vec::new();
+                let mut first_error = None; // keep checking the other parts so all errors are reported
+                for part in parts {
+                    match part {
+                        StrPart::Literal(s) => exprs.push(Expr::new(ExprKind::String(s), start.clone())),
+                        StrPart::Code { source, start: at } => match self.parse_interpolation(&source, at) {
+                            Ok(expr) => exprs.push(expr),
+                            Err(e) if first_error.is_none() => first_error = Some(e),
+                            Err(e) => self.errors.push(e),
+                        },
+                    }
+                }
+                if let Some(e) = first_error {
+                    return Err(e);
+                }
+                Expr::new(ExprKind::Interpolation(exprs), start.clone())
+            }
             Token::Bool(b) => {
-                let expr = Expr::Bool(*b);
+                let expr = Expr::new(ExprKind::Bool(*b), start.clone());
                 self.advance();
                 expr
             }
             Token::Range => {
                 self.advance(); // consume 'range'
                 if self.current != Token::LParen {
-                    panic!("Expected '(' after 'range', got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected '(' after 'range'"));
                 }
                 self.advance(); // consume '('
-                let arg = self.parse_expression(Precedence::Lowest);
+                let arg = self.parse_expression(Precedence::Lowest)?;
                 if self.current != Token::RParen {
-                    panic!("Expected ')' after range argument, got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected ')' after range argument"));
                 }
                 self.advance(); // consume ')'
-                Expr::Binary {
-                    left: Box::new(arg),
-                    op: Token::Range,
-                    right: Box::new(Expr::Number(0)), // Dummy right operand consumed in evaluator
+                let span = self.span_from(&start);
+                Expr::new(
+                    ExprKind::Binary {
+                        left: Box::new(arg),
+                        op: Token::Range,
+                        right: Box::new(Expr::new(ExprKind::Number(0), span.clone())), // Dummy right operand consumed in evaluator
+                    },
+                    span,
+                )
+            }
+            Token::Minus | Token::Plus | Token::Bang | Token::Not | Token::Tilde => {
+                let op = self.current.clone();
+                self.advance(); // consume the operator
+                let operand = self.parse_expression(Precedence::Prefix)?;
+                Expr::new(
+                    ExprKind::Unary {
+                        op,
+                        operand: Box::new(operand),
+                    },
+                    self.span_from(&start),
+                )
+            }
+            Token::LBracket => {
+                self.advance(); // consume '['
+                let mut elements = Vec::new();
+                while self.current != Token::RBracket {
+                    elements.push(self.parse_expression(Precedence::Lowest)?);
+                    if self.current != Token::Comma {
+                        break;
+                    }
+                    self.advance(); // consume ',', a trailing one is allowed
+                }
+                if self.current != Token::RBracket {
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected ']' after list elements"));
                 }
+                self.advance(); // consume ']'
+                Expr::new(ExprKind::List(elements), self.span_from(&start))
             }
             Token::LParen => {
                 self.advance();
-                let expr = self.parse_expression(Precedence::Lowest);
+                let expr = self.parse_expression(Precedence::Lowest)?;
                 // println!("expr: {:?}", expr);
                 if self.current != Token::RParen {
-                    panic!("Expected closing parenthesis, got {:?}", self.current);
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected closing parenthesis"));
                 }
                 self.advance();
                 expr
             }
             Token::LBrace => {
-                self.advance();
-                let expr = self.parse_expression(Precedence::Lowest);
-                if self.current != Token::RBrace {
-                    panic!("Expected closing brace, got {:?}", self.current);
+                self.advance(); // consume '{'
+                if self.current == Token::RBrace {
+                    self.advance(); // consume '}'
+                    Expr::new(ExprKind::Map(Vec::new()), self.span_from(&start))
+                } else {
+                    let expr = self.parse_expression(Precedence::Lowest)?;
+                    if self.current == Token::Colon {
+                        // `{key: ...` is a map; `{expr}` is grouping
+                        self.parse_map(expr, &start)?
+                    } else {
+                        if self.current != Token::RBrace {
+                            return Err(self.error(codes::EXPECTED_TOKEN, "Expected closing brace"));
+                        }
+                        self.advance();
+                        expr
+                    }
                 }
-                self.advance();
-                expr
+            }
+            Token::Identifier { name } if self.next == Token::FatArrow => {
+                // `x => expr` is short for `fn(x) { return expr; }`
+                let params = vec![name.clone()];
+                self.advance(); // consume the parameter
+                self.advance(); // consume '=>'
+                let value = self.in_function(&params, |parser| parser.parse_expression(Precedence::Lowest))?;
+                let body = Stmt::new(StmtKind::Return(Some(value)), self.span_from(&start));
+                Expr::new(ExprKind::Lambda { params, body: Rc::new(body) }, self.span_from(&start))
             }
             Token::Identifier { name } => {
-                let name_clone = name.clone();
+                let expr = Expr::new(ExprKind::Var(name.clone()), start.clone());
                 self.advance(); // consume identifier
-                if self.current == Token::LParen {
-                    let arguments = self.parse_call_arguments();
-                    Expr::Call {
-                        callee: name_clone,
-                        arguments,
+                expr
+            }
+            Token::Fn => {
+                self.advance(); // consume 'fn'
+                let params = self.parse_params()?;
+                let body = self.parse_function_body(&params)?;
+                Expr::new(ExprKind::Lambda { params, body }, self.span_from(&start))
+            }
+            Token::Super => {
+                self.advance(); // consume 'super'
+                if self.current != Token::Dot {
+                    return Err(self.error(codes::EXPECTED_TOKEN, "Expected '.' after 'super'"));
+                }
+                self.advance(); // consume '.'
+                let Token::Identifier { name: method } = self.current.clone() else {
+                    return Err(self.error(codes::EXPECTED_IDENTIFIER, "Expected method name after 'super.'"));
+                };
+                self.advance(); // consume the method name
+                let span = self.span_from(&start);
+                // not fatal: the rest of the method still parses
+                match self.classes.last() {
+                    Some(Some(true)) => {}
+                    Some(None) => self.errors.push(
+                        Diagnostic::error(codes::SUPER_OUTSIDE_METHOD, "`super` used outside a method", span.clone())
+                            .with_label("field defaults are evaluated before the object exists")
+                            .with_help("set the field in `init` instead"),
+                    ),
+                    enclosing => {
+                        let label = if enclosing.is_none() { "not inside a class" } else { "this class has no parent" };
+                        self.errors.push(
+                            Diagnostic::error(codes::SUPER_OUTSIDE_SUBCLASS, "`super` used outside a subclass", span.clone())
+                                .with_label(label),
+                        );
                     }
-                } else {
-                    Expr::Var(name_clone)
                 }
+                Expr::new(ExprKind::Super(method), span)
             }
-            other => panic!("Unexpected token in expression: {:?}", other),
+            _ => return Err(self.error(codes::EXPECTED_EXPRESSION, "Expected expression")),
         };
 
-        // precedence loop
+        // precedence loop: keep taking operators that bind tighter than `prec`
         while self.current != Token::EOF && precedence(&self.current) > prec {
+            if self.current == Token::LParen {
+                let arguments = self.parse_call_arguments()?;
+                left = Expr::new(ExprKind::Call { callee: Box::new(left), arguments }, self.span_from(&start));
+                continue;
+            }
+            if self.current == Token::Dot {
+                self.advance(); // consume '.'
+                let Token::Identifier { name } = self.current.clone() else {
+                    return Err(self.error(codes::EXPECTED_IDENTIFIER, "Expected field name after '.'"));
+                };
+                self.advance(); // consume the field name
+                left = Expr::new(ExprKind::Get { object: Box::new(left), name }, self.span_from(&start));
+                continue;
+            }
+            if self.current == Token::LBracket {
+                left = self.parse_index(left, &start)?;
+                continue;
+            }
+
+            if self.current == Token::Equal || compound_operator(&self.current).is_some() {
+                left = self.parse_assignment(left, &start)?;
+                continue;
+            }
+
             let op = self.current.clone();
+            let op_prec = precedence(&op);
             self.advance();
-            let right = self.parse_expression(precedence(&op));
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
+            let right = match associativity(&op) {
+                Associativity::Left => self.parse_expression(op_prec)?,
+                Associativity::Right => self.parse_expression(op_prec.lower())?,
             };
+            let kind = if matches!(op, Token::And | Token::Or) {
+                ExprKind::Logical { left: Box::new(left), op, right: Box::new(right) }
+            } else {
+                ExprKind::Binary { left: Box::new(left), op, right: Box::new(right) }
+            };
+            left = Expr::new(kind, self.span_from(&start));
+        }
+
+        Ok(left)
+    }
+
+    /// Parse the rest of a map literal once its first key has been read, up to the closing `}`
+    fn parse_map(&mut self, first_key: Expr, start: &Span) -> Result<Expr, ParseError> {
+        let mut entries = Vec::new();
+        let mut key = first_key;
+        loop {
+            if self.current != Token::Colon {
+                return Err(self.error(codes::EXPECTED_TOKEN, "Expected ':' after map key"));
+            }
+            self.advance(); // consume ':'
+            let value = self.parse_expression(Precedence::Lowest)?;
+            entries.push((key, value));
+
+            if self.current != Token::Comma {
+                break;
+            }
+            self.advance(); // consume ','
+            if self.current == Token::RBrace {
+                break; // trailing comma
+            }
+            key = self.parse_expression(Precedence::Lowest)?;
+        }
+        if self.current != Token::RBrace {
+            return Err(self.error(codes::EXPECTED_TOKEN, "Expected '}' after map entries"));
+        }
+        self.advance(); // consume '}'
+        Ok(Expr::new(ExprKind::Map(entries), self.span_from(start)))
+    }
+
+    /// Parse `[index]` or `[start:end]` after `object`; either end of a slice can be left out
+    fn parse_index(&mut self, object: Expr, start: &Span) -> Result<Expr, ParseError> {
+        self.advance(); // consume '['
+        let first = if self.current == Token::Colon {
+            None
+        } else {
+            Some(Box::new(self.parse_expression(Precedence::Lowest)?))
+        };
+
+        let kind = match first {
+            Some(index) if self.current != Token::Colon => ExprKind::Index { object: Box::new(object), index },
+            first => {
+                self.advance(); // consume ':'
+                let end = if self.current == Token::RBracket {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expression(Precedence::Lowest)?))
+                };
+                ExprKind::Slice { object: Box::new(object), start: first, end }
+            }
+        };
+
+        if self.current != Token::RBracket {
+            return Err(self.error(codes::EXPECTED_TOKEN, "Expected ']' after index"));
         }
+        self.advance(); // consume ']'
+        Ok(Expr::new(kind, self.span_from(start)))
+    }
 
-        left
+    /// Parse `= value` or `+= value` and friends after an assignment target.
+    /// Assignment nests to the right, so `a = b = 0` sets both.
+    fn parse_assignment(&mut self, target: Expr, start: &Span) -> Result<Expr, ParseError> {
+        let op = compound_operator(&self.current);
+        let name = match target.kind {
+            ExprKind::Var(name) => name,
+            ExprKind::Index { object, index } => {
+                self.advance(); // consume '=' or the compound operator
+                let value = Box::new(self.parse_expression(Precedence::Assignment.lower())?);
+                return Ok(Expr::new(ExprKind::SetIndex { object, index, op, value }, self.span_from(start)));
+            }
+            ExprKind::Get { object, name } => {
+                self.advance(); // consume '=' or the compound operator
+                let value = Box::new(self.parse_expression(Precedence::Assignment.lower())?);
+                return Ok(Expr::new(ExprKind::Set { object, name, op, value }, self.span_from(start)));
+            }
+            _ => {
+                return Err(Diagnostic::error(codes::INVALID_ASSIGNMENT_TARGET, "Invalid assignment target", target.span)
+                    .with_label("cannot assign to this expression"));
+            }
+        };
+        if let Some(declared) = self.constant_declaration(&name) {
+            // not fatal: the rest of the expression still parses
+            self.errors.push(
+                Diagnostic::error(codes::ASSIGN_TO_CONSTANT, format!("Cannot assign to constant: {}", name), target.span.clone())
+                    .with_label("cannot assign twice to a constant")
+                    .with_note(format!("`{}` is declared with `const` at {}", name, declared))
+                    .with_help(format!("declare it with `var {} = ...` if it needs to change", name)),
+            );
+        }
+        self.advance(); // consume '=' or the compound operator
+        let value = self.parse_expression(Precedence::Assignment.lower())?;
+        Ok(Expr::new(ExprKind::Assign { name, op, value: Box::new(value) }, self.span_from(start)))
+    }
+
+    /// Parse the expression inside `${...}` with a parser of its own.
+    /// Its lexer starts where the code sits in the file, so spans and errors point at the right place,
+    /// and it borrows this parser's scopes, loops and classes so the code is checked like any other.
+    fn parse_interpolation(&mut self, source: &str, at: (usize, usize, usize)) -> Result<Expr, ParseError> {
+        let mut parser = Parser::new(Lexer::at(source, Rc::clone(&self.current_span.file), at));
+        parser.scopes = std::mem::take(&mut self.scopes);
+        parser.loops = std::mem::take(&mut self.loops);
+        parser.classes = std::mem::take(&mut self.classes);
+        let result = parser.parse_expression(Precedence::Lowest).and_then(|expr| {
+            if parser.current != Token::EOF {
+                return Err(parser.error(codes::EXPECTED_TOKEN, "Expected '}' to close interpolation"));
+            }
+            Ok(expr)
+        });
+        self.scopes = std::mem::take(&mut parser.scopes);
+        self.loops = std::mem::take(&mut parser.loops);
+        self.classes = std::mem::take(&mut parser.classes);
+
+        // errors the sub-parser recovered from count even when the expression itself parsed
+        let mut errors = parser.lexer.take_errors();
+        let expr = match result {
+            Ok(expr) if errors.is_empty() => Some(expr),
+            Ok(_) => None,
+            Err(e) => {
+                parser.record(e);
+                None
+            }
+        };
+        errors.append(&mut parser.errors);
+        errors.sort_by_key(|e| e.span.start);
+        match expr {
+            Some(expr) => {
+                self.errors.extend(errors);
+                Ok(expr)
+            }
+            None => {
+                let first = errors.remove(0);
+                self.errors.extend(errors);
+                Err(first)
+            }
+        }
     }
 
-    fn parse_call_arguments(&mut self) -> Vec<Expr> {
+    fn parse_call_arguments(&mut self) -> Result<Vec<Expr>, ParseError> {
         if self.current != Token::LParen {
-            panic!("Expected '(' to start argument list, got {:?}", self.current);
+            return Err(self.error(codes::EXPECTED_TOKEN, "Expected '(' to start argument list"));
         }
         self.advance(); // consume '('
 
         let mut arguments = Vec::new();
         if self.current != Token::RParen {
             loop {
-                let arg = self.parse_expression(Precedence::Lowest);
+                let arg = self.parse_expression(Precedence::Lowest)?;
                 arguments.push(arg);
 
                 if self.current == Token::Comma {
@@ -364,35 +821,550 @@ impl Parser {
         }
 
         if self.current != Token::RParen {
-            panic!("Expected ')' after arguments, got {:?}", self.current);
+            return Err(self.error(codes::EXPECTED_TOKEN, "Expected ')' after arguments"));
         }
         self.advance(); // consume ')'
 
-        arguments
+        Ok(arguments)
     }
 
-    /// Parse a block of statements until closing brace
-    fn parse_block(&mut self) -> Stmt {
+    /// Parse fields and methods up to the `}` that closes a class body
+    fn parse_class_body(&mut self) -> Result<(Vec<Field>, Vec<Stmt>), ParseError> {
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        while self.current != Token::RBrace && self.current != Token::EOF {
+            match self.current.clone() {
+                Token::Fn => {
+                    let method_start = self.current_span.clone();
+                    let name_span = self.next_span.clone();
+                    let method = self.parse_function(method_start)?;
+                    if let StmtKind::Function { name, params, .. } = &method.kind
+                        && params.first().map(String::as_str) != Some("self")
+                    {
+                        self.errors.push(
+                            Diagnostic::error(
+                                codes::METHOD_WITHOUT_SELF,
+                                format!("Method '{}' must take `self` as its first parameter", name),
+                                name_span,
+                            )
+                            .with_label("missing `self` parameter")
+                            .with_help(format!("write it as `fn {}(self{}{})`", name, if params.is_empty() { "" } else { ", " }, params.join(", "))),
+                        );
+                    }
+                    methods.push(method);
+                }
+                Token::Identifier { name } => {
+                    self.advance(); // consume the field name
+                    let default = if self.current == Token::Equal {
+                        self.advance(); // consume '='
+                        // a default has no `self`, so it cannot call `super` either
+                        self.classes.push(None);
+                        let default = self.parse_expression(Precedence::Lowest);
+                        self.classes.pop();
+                        Some(default?)
+                    } else {
+                        None
+                    };
+                    if self.current != Token::Semicolon {
+                        return Err(self.error(codes::EXPECTED_SEMICOLON, "Expected ';' after field"));
+                    }
+                    self.advance(); // consume ';'
+                    fields.push((name, default));
+                }
+                _ => return Err(self.error(codes::EXPECTED_TOKEN, "Expected a field or method in class body")),
+            }
+        }
+        Ok((fields, methods))
+    }
+
+    /// Parse a block of statements until closing brace.
+    /// Errors inside the block are recorded and skipped so the rest of the block still gets checked.
+    fn parse_block(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.prev_span.clone(); // the '{' that opened the block
         let mut stmts = Vec::new();
+        self.scopes.push(HashMap::new());
         
         while self.current != Token::RBrace && self.current != Token::EOF {
-            stmts.push(self.parse_stmt());
+            match self.parse_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(e) => {
+                    self.record(e);
+                    self.synchronize();
+                }
+            }
         }
+        self.scopes.pop();
         
         if self.current != Token::RBrace {
-            panic!("Expected '}}' at end of block, got {:?}", self.current);
+            return Err(self.error(codes::EXPECTED_TOKEN, "Expected '}' at end of block"));
         }
         self.advance(); // consume '}'
         
-        Stmt::Block(stmts)
+        Ok(Stmt::new(StmtKind::Block(stmts), self.span_from(&start)))
     }
 
-    /// Parse a *program* (zero or more statements) until EOF
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    /// Skip tokens after a syntax error until a point where parsing can resume:
+    /// just past a `;`, at a `}` closing the enclosing block, or at a keyword that starts a statement.
+    /// A `{` opened while skipping is skipped up to its matching `}`, so the rest of a failed
+    /// function or `if` (with its `else` branches) is not parsed as statements of its own.
+    fn synchronize(&mut self) {
+        let error_pos = self.current_span.start;
+        let mut depth = 0;
+        while self.current != Token::EOF {
+            match self.current {
+                Token::LBrace => {
+                    depth += 1;
+                    self.advance(); // consume '{'
+                }
+                Token::RBrace if depth > 0 => {
+                    depth -= 1;
+                    self.advance(); // consume '}'
+                    // an `else` or `elif` still belongs to the failed `if`
+                    if depth == 0 && !matches!(self.current, Token::Else | Token::Elif) {
+                        return;
+                    }
+                }
+                _ if depth > 0 => self.advance(),
+                Token::Semicolon => {
+                    self.advance(); // consume ';'
+                    return;
+                }
+                Token::RBrace => return,
+                Token::Var | Token::Const | Token::Fn | Token::Class | Token::For | Token::While | Token::If | Token::Return | Token::Print
+                    | Token::Break | Token::Continue
+                    if self.current_span.start != error_pos =>
+                {
+                    return;
+                }
+                _ => self.advance(),
+            }
+        }
+    }
+
+    /// Keep an error that made a statement fail.
+    /// Errors are raised at the current token, and an `Illegal` token has already been reported by the lexer.
+    fn record(&mut self, error: ParseError) {
+        if !matches!(self.current, Token::Illegal(_)) {
+            self.errors.push(error);
+        }
+    }
+
+    /// Build an error pointing at the current token
+    fn error(&self, code: &'static str, message: &str) -> ParseError {
+        Diagnostic::error(code, format!("{}, got {}", message, self.current), self.current_span.clone())
+            .with_label(format!("unexpected {}", self.current))
+    }
+
+    /// Parse a *program* (zero or more statements) until EOF.
+    /// Returns every syntax error found in the file rather than stopping at the first one.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
         let mut stmts = Vec::new();
         while self.current != Token::EOF {
-            stmts.push(self.parse_stmt());
+            match self.parse_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(e) => {
+                    self.record(e);
+                    self.synchronize();
+                    if self.current == Token::RBrace {
+                        self.advance(); // stray '}' with no block to close
+                    }
+                }
+            }
         }
-        stmts
+
+        let mut errors = self.lexer.take_errors();
+        errors.append(&mut self.errors);
+        if errors.is_empty() {
+            Ok(stmts)
+        } else {
+            errors.sort_by_key(|e| e.span.start);
+            Err(errors)
+        }
+    }
+}
+
+/// A syntax error and where it was found
+pub type ParseError = Diagnostic;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse `src` as a single expression and print it as an S-expression, e.g. `(+ a (* b c))`
+    fn tree(src: &str) -> String {
+        let mut parser = Parser::new(Lexer::new(src, "<test>"));
+        let expr = parser.parse_expression(Precedence::Lowest).expect("parse error");
+        assert_eq!(parser.current, Token::EOF, "unparsed input in {src:?}");
+        sexpr(&expr)
+    }
+
+    /// Parse the program `src`, failing if it has no errors, and return the errors in source order
+    fn parse_errors(src: &str) -> Vec<Diagnostic> {
+        Parser::new(Lexer::new(src, "<test>")).parse().expect_err(&format!("no error parsing {src:?}"))
+    }
+
+    /// The first error in the program `src`
+    fn parse_err(src: &str) -> Diagnostic {
+        parse_errors(src).remove(0)
+    }
+
+    fn sexpr(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Number(n) => n.to_string(),
+            ExprKind::Var(name) => name.clone(),
+            ExprKind::Bool(b) => b.to_string(),
+            ExprKind::Unary { op, operand } => format!("({} {})", symbol(op), sexpr(operand)),
+            ExprKind::Binary { left, op, right } | ExprKind::Logical { left, op, right } => {
+                format!("({} {} {})", symbol(op), sexpr(left), sexpr(right))
+            }
+            ExprKind::List(elements) => {
+                let items: Vec<String> = elements.iter().map(sexpr).collect();
+                format!("[{}]", items.join(" "))
+            }
+            ExprKind::Map(entries) => {
+                let items: Vec<String> = entries.iter().map(|(k, v)| format!("{}: {}", sexpr(k), sexpr(v))).collect();
+                format!("{{{}}}", items.join(", "))
+            }
+            ExprKind::Index { object, index } => format!("(index {} {})", sexpr(object), sexpr(index)),
+            ExprKind::Slice { object, start, end } => {
+                let bound = |b: &Option<Box<Expr>>| b.as_deref().map_or("_".to_string(), sexpr);
+                format!("(slice {} {} {})", sexpr(object), bound(start), bound(end))
+            }
+            ExprKind::SetIndex { object, index, op, value } => {
+                let op = op.as_ref().map_or(String::new(), symbol);
+                format!("({op}= (index {} {}) {})", sexpr(object), sexpr(index), sexpr(value))
+            }
+            ExprKind::Get { object, name } => format!("(. {} {name})", sexpr(object)),
+            ExprKind::Set { object, name, op, value } => {
+                let op = op.as_ref().map_or(String::new(), symbol);
+                format!("({op}= (. {} {name}) {})", sexpr(object), sexpr(value))
+            }
+            ExprKind::Super(method) => format!("(. super {method})"),
+            ExprKind::Lambda { params, .. } => format!("(fn {})", params.join(" ")).replace(" )", ")"),
+            ExprKind::Assign { name, op: None, value } => format!("(= {name} {})", sexpr(value)),
+            ExprKind::Assign { name, op: Some(op), value } => format!("({}= {name} {})", symbol(op), sexpr(value)),
+            ExprKind::Call { callee, arguments } => {
+                let args: Vec<String> = arguments.iter().map(sexpr).collect();
+                format!("({} {})", sexpr(callee), args.join(" ")).replace(" )", ")")
+            }
+            other => panic!("no S-expression form for {other:?}"),
+        }
+    }
+
+    fn symbol(op: &Token) -> String {
+        op.to_string().trim_matches('\'').to_string()
+    }
+
+    #[test]
+    fn or_is_looser_than_and() {
+        assert_eq!(tree("a or b and c"), "(or a (and b c))");
+        assert_eq!(tree("a and b || c"), "(or (and a b) c)");
+        assert_eq!(tree("a or b or c"), "(or (or a b) c)");
+    }
+
+    #[test]
+    fn and_is_looser_than_equality() {
+        assert_eq!(tree("a and b == c"), "(and a (== b c))");
+        assert_eq!(tree("a != b && c"), "(and (!= a b) c)");
+        assert_eq!(tree("a and b and c"), "(and (and a b) c)");
+    }
+
+    #[test]
+    fn equality_is_looser_than_comparison() {
+        assert_eq!(tree("a == b < c"), "(== a (< b c))");
+        assert_eq!(tree("a >= b != c"), "(!= (>= a b) c)");
+        assert_eq!(tree("a == b != c"), "(!= (== a b) c)");
+    }
+
+    #[test]
+    fn comparison_is_looser_than_sum() {
+        assert_eq!(tree("a < b + c"), "(< a (+ b c))");
+        assert_eq!(tree("a - b > c"), "(> (- a b) c)");
+        assert_eq!(tree("a < b <= c"), "(<= (< a b) c)");
+    }
+
+    #[test]
+    fn bitwise_levels_sit_between_comparison_and_sum() {
+        assert_eq!(tree("a == b | c"), "(== a (| b c))");
+        assert_eq!(tree("a | b ^ c"), "(| a (^ b c))");
+        assert_eq!(tree("a ^ b & c"), "(^ a (& b c))");
+        assert_eq!(tree("a & b << c"), "(& a (<< b c))");
+        assert_eq!(tree("a >> b + c"), "(>> a (+ b c))");
+        assert_eq!(tree("a << b >> c"), "(>> (<< a b) c)");
+    }
+
+    #[test]
+    fn sum_is_looser_than_product() {
+        assert_eq!(tree("a + b * c"), "(+ a (* b c))");
+        assert_eq!(tree("a / b - c"), "(- (/ a b) c)");
+        assert_eq!(tree("a - b + c"), "(+ (- a b) c)");
+    }
+
+    #[test]
+    fn product_is_left_associative() {
+        assert_eq!(tree("a / b / c"), "(/ (/ a b) c)");
+        assert_eq!(tree("a * b / c"), "(/ (* a b) c)");
+    }
+
+    #[test]
+    fn product_binds_tighter_than_comparison() {
+        // `a * b < c` used to parse as `a * (b < c)`
+        assert_eq!(tree("a * b < c"), "(< (* a b) c)");
+        assert_eq!(tree("2 * 3 == 6"), "(== (* 2 3) 6)");
+    }
+
+    #[test]
+    fn floor_division_and_modulo_share_the_product_level() {
+        assert_eq!(tree("a + b % c"), "(+ a (% b c))");
+        assert_eq!(tree("a // b * c"), "(* (// a b) c)");
+        assert_eq!(tree("a % b // c"), "(// (% a b) c)");
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_eq!(tree("a ** b ** c"), "(** a (** b c))");
+        assert_eq!(tree("a * b ** c"), "(* a (** b c))");
+        assert_eq!(tree("a ** b * c"), "(* (** a b) c)");
+        assert_eq!(tree("a ** -b"), "(** a (- b))");
+    }
+
+    #[test]
+    fn prefix_binds_tighter_than_infix() {
+        assert_eq!(tree("-a * b"), "(* (- a) b)");
+        assert_eq!(tree("!a == b"), "(== (! a) b)");
+        assert_eq!(tree("not a and b"), "(and (not a) b)");
+        assert_eq!(tree("- -a"), "(- (- a))");
+        assert_eq!(tree("~a & b"), "(& (~ a) b)");
+        assert_eq!(tree("-a ** b"), "(** (- a) b)");
+        assert_eq!(tree("a - -b"), "(- a (- b))");
+    }
+
+    #[test]
+    fn call_binds_tighter_than_prefix() {
+        assert_eq!(tree("-f(x)"), "(- (f x))");
+        assert_eq!(tree("f(a + b, c) * 2"), "(* (f (+ a b) c) 2)");
+        assert_eq!(tree("f()"), "(f)");
+    }
+
+    #[test]
+    fn indexing_is_postfix_like_a_call() {
+        assert_eq!(tree("-xs[0]"), "(- (index xs 0))");
+        assert_eq!(tree("xs[i + 1] * 2"), "(* (index xs (+ i 1)) 2)");
+        assert_eq!(tree("grid[r][c]"), "(index (index grid r) c)");
+        assert_eq!(tree("f(x)[0]"), "(index (f x) 0)");
+        assert_eq!(tree("[a, b + c,][1]"), "(index [a (+ b c)] 1)");
+    }
+
+    #[test]
+    fn braces_hold_a_map_or_a_grouped_expression() {
+        assert_eq!(tree("{a: 1, b + c: d,}"), "{a: 1, (+ b c): d}");
+        assert_eq!(tree("{}"), "{}");
+        assert_eq!(tree("{a + b} * c"), "(* (+ a b) c)");
+        assert_eq!(tree("{k: v}[k]"), "(index {k: v} k)");
+    }
+
+    #[test]
+    fn in_is_a_comparison() {
+        assert_eq!(tree("k in m and ok"), "(and (in k m) ok)");
+        assert_eq!(tree("a + b in xs"), "(in (+ a b) xs)");
+        assert_eq!(tree("k in m == true"), "(== (in k m) true)");
+    }
+
+    #[test]
+    fn slices_may_leave_out_either_bound() {
+        assert_eq!(tree("xs[a:b]"), "(slice xs a b)");
+        assert_eq!(tree("xs[:n - 1]"), "(slice xs _ (- n 1))");
+        assert_eq!(tree("xs[-2:]"), "(slice xs (- 2) _)");
+        assert_eq!(tree("xs[:]"), "(slice xs _ _)");
+    }
+
+    #[test]
+    fn index_assignment_targets_the_element() {
+        assert_eq!(tree("xs[i] = v + 1"), "(= (index xs i) (+ v 1))");
+        assert_eq!(tree("xs[0][1] += 2"), "(+= (index (index xs 0) 1) 2)");
+    }
+
+    #[test]
+    fn member_access_is_postfix() {
+        assert_eq!(tree("p.age + 1"), "(+ (. p age) 1)");
+        assert_eq!(tree("-a.b.c"), "(- (. (. a b) c))");
+        assert_eq!(tree("p.pets[0].name"), "(. (index (. p pets) 0) name)");
+        assert_eq!(tree("p.greet(a, b).len"), "(. ((. p greet) a b) len)");
+        assert_eq!(tree("p.intro()"), "((. p intro))");
+    }
+
+    #[test]
+    fn field_assignment_targets_the_field() {
+        assert_eq!(tree("p.age = p.age + 1"), "(= (. p age) (+ (. p age) 1))");
+        assert_eq!(tree("self.count += 1"), "(+= (. self count) 1)");
+    }
+
+    #[test]
+    fn methods_must_take_self() {
+        assert_eq!(parse_err("class A() { x; fn get() { return 1; } }").code, codes::METHOD_WITHOUT_SELF);
+    }
+
+    #[test]
+    fn is_is_a_comparison() {
+        assert_eq!(tree("p is Employee and ok"), "(and (is p Employee) ok)");
+        assert_eq!(tree("a.b is C == true"), "(== (is (. a b) C) true)");
+    }
+
+    #[test]
+    fn super_needs_a_parent_class() {
+        assert_eq!(parse_err("class A() { fn f(self) { return super.f(); } }").code, codes::SUPER_OUTSIDE_SUBCLASS);
+        let mut parser = Parser::new(Lexer::new("class B(A) { fn f(self) { return super.f(1) + 1; } }", "<test>"));
+        assert!(parser.parse().is_ok());
+    }
+
+    #[test]
+    fn super_is_only_allowed_in_methods() {
+        let error = parse_err("class B(A) {\n    n = super.size();\n}");
+        assert_eq!(error.code, codes::SUPER_OUTSIDE_METHOD);
+        assert_eq!((error.span.line, error.span.column), (2, 9));
+        assert_eq!(parse_err("class B(A) { f = fn() { return super.f(); }; }").code, codes::SUPER_OUTSIDE_METHOD);
+        assert_eq!(parse_err("print(super.f());").code, codes::SUPER_OUTSIDE_SUBCLASS);
+    }
+
+    #[test]
+    fn any_expression_can_be_called() {
+        assert_eq!(tree("f(a)(b)"), "((f a) b)");
+        assert_eq!(tree("ops[k](x, y)"), "((index ops k) x y)");
+        assert_eq!(tree("super.init(n)"), "((. super init) n)");
+    }
+
+    #[test]
+    fn lambdas_are_expressions() {
+        assert_eq!(tree("x => x * 2 + 1"), "(fn x)");
+        assert_eq!(tree("apply(x => x, y)"), "(apply (fn x) y)");
+        assert_eq!(tree("fn(a, b) { return a; }(1, 2)"), "((fn a b) 1 2)");
+        assert_eq!(tree("fn() {}"), "(fn)");
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert_eq!(tree("(a + b) * c"), "(* (+ a b) c)");
+        assert_eq!(tree("a - (b - c)"), "(- a (- b c))");
+        assert_eq!(tree("-(a + b)"), "(- (+ a b))");
+    }
+
+    #[test]
+    fn assignment_is_loosest_and_right_associative() {
+        assert_eq!(tree("x = a or b == c"), "(= x (or a (== b c)))");
+        assert_eq!(tree("a = b = c"), "(= a (= b c))");
+        assert_eq!(tree("f(x = 1)"), "(f (= x 1))");
+    }
+
+    #[test]
+    fn compound_assignment_takes_the_whole_right_side() {
+        assert_eq!(tree("x += a * b"), "(+= x (* a b))");
+        assert_eq!(tree("x **= 2 ** 3"), "(**= x (** 2 3))");
+        assert_eq!(tree("x = y <<= 1"), "(= x (<<= y 1))");
+        assert_eq!(tree("x //= a % b"), "(//= x (% a b))");
+    }
+
+    #[test]
+    fn assignment_needs_a_variable_target() {
+        assert_eq!(parse_err("a + b = c;").code, codes::INVALID_ASSIGNMENT_TARGET);
+    }
+
+    #[test]
+    fn assigning_a_constant_is_a_parse_error() {
+        let errors = parse_errors("const MAX = 3;\nMAX += 1;\nif (true) { var MAX = 0; MAX = 1; }");
+        assert_eq!(errors.len(), 1, "the shadowing `var` is mutable");
+        assert_eq!(errors[0].code, codes::ASSIGN_TO_CONSTANT);
+        assert_eq!(errors[0].span.line, 2);
+    }
+
+    #[test]
+    fn recovery_skips_the_body_of_a_failed_statement() {
+        let source = "fn f(a: int) {\n    return a;\n}\nif (x > ) {\n    print(1);\n} else {\n    print(2);\n}\nvar y = ;";
+        let errors = parse_errors(source);
+        let lines: Vec<usize> = errors.iter().map(|e| e.span.line).collect();
+        assert_eq!(lines, [1, 4, 9]);
+    }
+
+    #[test]
+    fn redeclaring_a_constant_is_a_parse_error() {
+        let source = "const MAX = 3;\nvar MAX = 10;\nfn MAX() {}\nif (true) { var MAX = 0; }";
+        let errors = parse_errors(source);
+        let lines: Vec<usize> = errors.iter().map(|e| e.span.line).collect();
+        assert_eq!(lines, [2, 3], "shadowing in a block is allowed");
+        assert!(errors.iter().all(|e| e.code == codes::ASSIGN_TO_CONSTANT));
+    }
+
+    #[test]
+    fn errors_inside_interpolation_are_reported() {
+        // the lambda's body recovers from the error, so the interpolation itself parses
+        let error = parse_err("print(\"${fn() { var = ; return 1; }()}\");");
+        assert_eq!(error.code, codes::EXPECTED_IDENTIFIER);
+        assert_eq!((error.span.line, error.span.column), (1, 21));
+
+        let error = parse_err("const K = 1;\nprint(\"${K = 2}\");");
+        assert_eq!(error.code, codes::ASSIGN_TO_CONSTANT);
+        assert_eq!((error.span.line, error.span.column), (2, 10));
+
+        let errors = parse_errors("for i in range(2) {\n    print(\"${fn() { break; }} ${super.f()}\");\n}");
+        let found: Vec<_> = errors.iter().map(|e| (e.code, e.span.line, e.span.column)).collect();
+        assert_eq!(found, [(codes::BREAK_OUTSIDE_LOOP, 2, 21), (codes::SUPER_OUTSIDE_SUBCLASS, 2, 33)]);
+    }
+
+    #[test]
+    fn interpolation_sees_the_enclosing_class() {
+        let source = "class B(A) { fn f(self) { return \"${super.f()}\"; } }";
+        assert!(Parser::new(Lexer::new(source, "<test>")).parse().is_ok());
+    }
+
+    #[test]
+    fn break_and_continue_need_a_loop() {
+        let error = parse_err("var x = 1;\nbreak;");
+        assert_eq!(error.code, codes::BREAK_OUTSIDE_LOOP);
+        assert_eq!((error.span.line, error.span.column, error.span.end - error.span.start), (2, 1, 6));
+
+        let error = parse_err("if (true) {\n    continue;\n}");
+        assert_eq!(error.code, codes::BREAK_OUTSIDE_LOOP);
+        assert_eq!((error.span.line, error.span.column, error.span.end - error.span.start), (2, 5, 9));
+    }
+
+    #[test]
+    fn a_function_cannot_break_the_loop_around_it() {
+        let error = parse_err("for i in range(3) {\n    fn f() { break; }\n}");
+        assert_eq!(error.code, codes::BREAK_OUTSIDE_LOOP);
+        assert_eq!((error.span.line, error.span.column), (2, 14));
+
+        let error = parse_err("outer: while (true) {\n    var f = x => fn() { continue outer; };\n}");
+        assert_eq!(error.code, codes::BREAK_OUTSIDE_LOOP);
+        assert_eq!((error.span.line, error.span.column), (2, 25));
+    }
+
+    #[test]
+    fn loop_labels_must_name_an_enclosing_loop() {
+        let error = parse_err("outer: for i in range(3) {\n    while (true) { break outr; }\n}");
+        assert_eq!(error.code, codes::UNDEFINED_LABEL);
+        assert_eq!((error.span.line, error.span.column, error.span.end - error.span.start), (2, 20, 11));
+        assert_eq!(error.help, ["did you mean `outer`?"]);
+
+        let error = parse_err("outer: for i in range(3) {}\nfor j in range(3) { continue outer; }");
+        assert_eq!(error.code, codes::UNDEFINED_LABEL);
+        assert_eq!((error.span.line, error.span.column), (2, 21));
+    }
+
+    #[test]
+    fn var_declaration_is_not_an_assignment() {
+        let mut parser = Parser::new(Lexer::new("var x = y = 2;", "<test>"));
+        let program = parser.parse().expect("parse error");
+        let StmtKind::VarDecl { name, value, .. } = &program[0].kind else {
+            panic!("expected a declaration, got {:?}", program[0].kind);
+        };
+        assert_eq!(name, "x");
+        assert_eq!(sexpr(value), "(= y 2)");
+    }
+
+    #[test]
+    fn lower_steps_down_one_level() {
+        assert_eq!(Precedence::Power.lower(), Precedence::Product);
+        assert_eq!(Precedence::Or.lower(), Precedence::Assignment);
+        assert_eq!(Precedence::Lowest.lower(), Precedence::Lowest);
+        assert_eq!(associativity(&Token::Minus), Associativity::Left);
     }
 }
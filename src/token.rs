@@ -1,24 +1,40 @@
-
+use std::rc::Rc;
 
 #[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Token {
     // Single-character symbols
     LParen, RParen,
     LBrace, RBrace,
-    Plus, Minus, Star, Slash,
+    LBracket, RBracket,
+    Plus, Minus, Star, Slash, Percent,
+    Ampersand, Pipe, Caret, Tilde, // bitwise and, or, xor, not
     Equal,
+    Bang,
     Semicolon,
+    Colon,
+    Dot,
     Comma,
 
     // Multi-character symbols
     DblEqual, // Double equal for equality check
     NotEqual, // Not equal check
     LessThan, GreaterThan, LessThanEqual, GreaterThanEqual,
+    And, Or, // `and` / `&&`, `or` / `||`
+    DblStar,  // `**` exponent
+    DblSlash, // `//` floor division
+    FatArrow, // `=>` in `x => x * 2`
+    ShiftLeft, ShiftRight,
+    // compound assignment, `x += 1` is `x = x + 1`
+    PlusEqual, MinusEqual, StarEqual, SlashEqual, PercentEqual, DblStarEqual, DblSlashEqual,
+    AmpersandEqual, PipeEqual, CaretEqual, ShiftLeftEqual, ShiftRightEqual,
 
     // types
     Number(i64),
     Float(f64),
     String(String),
+    Interpolated(Vec<StrPart>), // string containing `${...}`
+    Char(char),
     Bool(bool),
 
     // Keywords
@@ -26,11 +42,17 @@ pub enum Token {
     Else,
     Elif,
     Fn,
+    Class,
+    Super,
     Return,
-    // While,
+    While,
     For,
+    Break,
+    Continue,
     In,
+    Is, // `object is Class`
     Range,
+    Not,
     // Function, // Function definition
     // Return,
 
@@ -39,6 +61,7 @@ pub enum Token {
 
     // identifiers
     Var,
+    Const,
     Identifier {
         name: String,
     },
@@ -48,3 +71,130 @@ pub enum Token {
     EOF,           // End of file
     Illegal(char), // Illegal character
 }
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let symbol = match self {
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LBrace => "{",
+            Token::RBrace => "}",
+            Token::LBracket => "[",
+            Token::RBracket => "]",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::Ampersand => "&",
+            Token::Pipe => "|",
+            Token::Caret => "^",
+            Token::Tilde => "~",
+            Token::Equal => "=",
+            Token::Bang => "!",
+            Token::Semicolon => ";",
+            Token::Colon => ":",
+            Token::Dot => ".",
+            Token::Comma => ",",
+            Token::DblEqual => "==",
+            Token::NotEqual => "!=",
+            Token::LessThan => "<",
+            Token::GreaterThan => ">",
+            Token::LessThanEqual => "<=",
+            Token::GreaterThanEqual => ">=",
+            Token::And => "and",
+            Token::Or => "or",
+            Token::DblStar => "**",
+            Token::DblSlash => "//",
+            Token::FatArrow => "=>",
+            Token::ShiftLeft => "<<",
+            Token::ShiftRight => ">>",
+            Token::PlusEqual => "+=",
+            Token::MinusEqual => "-=",
+            Token::StarEqual => "*=",
+            Token::SlashEqual => "/=",
+            Token::PercentEqual => "%=",
+            Token::DblStarEqual => "**=",
+            Token::DblSlashEqual => "//=",
+            Token::AmpersandEqual => "&=",
+            Token::PipeEqual => "|=",
+            Token::CaretEqual => "^=",
+            Token::ShiftLeftEqual => "<<=",
+            Token::ShiftRightEqual => ">>=",
+            Token::If => "if",
+            Token::Else => "else",
+            Token::Elif => "elif",
+            Token::Fn => "fn",
+            Token::Class => "class",
+            Token::Super => "super",
+            Token::Return => "return",
+            Token::While => "while",
+            Token::For => "for",
+            Token::Break => "break",
+            Token::Continue => "continue",
+            Token::In => "in",
+            Token::Is => "is",
+            Token::Range => "range",
+            Token::Not => "not",
+            Token::Print => "print",
+            Token::Var => "var",
+            Token::Const => "const",
+            Token::Number(n) => return write!(f, "number {}", n),
+            Token::Float(n) => return write!(f, "number {}", n),
+            Token::String(s) => return write!(f, "string {:?}", s),
+            Token::Interpolated(_) => return write!(f, "string"),
+            Token::Char(c) => return write!(f, "character {:?}", c),
+            Token::Bool(b) => return write!(f, "'{}'", b),
+            Token::Identifier { name } => return write!(f, "identifier '{}'", name),
+            Token::EOF => return write!(f, "end of file"),
+            Token::Illegal(c) => return write!(f, "'{}'", c),
+        };
+        write!(f, "'{}'", symbol)
+    }
+}
+
+/// A piece of an interpolated string literal
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrPart {
+    Literal(String),
+    // Source of a `${...}` expression and the line, column and byte offset it starts at
+    Code { source: String, start: (usize, usize, usize) },
+}
+
+/// A region of source text.
+/// `line` and `column` are 1-based and point at the first character,
+/// `start..end` is the byte range the region covers in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub file: Rc<str>,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Join two spans into one covering everything from `self` up to the end of `other`
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            file: Rc::clone(&self.file),
+            line: self.line,
+            column: self.column,
+            start: self.start,
+            end: other.end.max(self.end),
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// A token together with where it was found in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
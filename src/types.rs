@@ -1,16 +1,110 @@
+use crate::codes;
+use crate::enviorment::{Class, Function, Instance};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
+use std::rc::Rc;
+use std::thread::LocalKey;
 
 
-#[derive(Debug, Clone, PartialEq)]
+/// Why an operation on values failed; the evaluator attaches the span
+#[derive(Debug)]
+pub struct ValueError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ValueError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        ValueError { code, message: message.into() }
+    }
+
+    fn invalid_operands(operation: &str, a: &Type, b: &Type) -> Self {
+        ValueError::new(
+            codes::INVALID_OPERANDS,
+            format!("Invalid types for {}: {} and {}", operation, a.type_name(), b.type_name()),
+        )
+    }
+
+    fn invalid_operand(operation: &str, a: &Type) -> Self {
+        ValueError::new(
+            codes::INVALID_OPERANDS,
+            format!("Invalid type for {}: {}", operation, a.type_name()),
+        )
+    }
+
+    fn overflow() -> Self {
+        ValueError::new(codes::INTEGER_OVERFLOW, "Integer overflow")
+    }
+
+    fn division_by_zero() -> Self {
+        ValueError::new(codes::DIVISION_BY_ZERO, "Division by zero")
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Type {
     Int(i64),
     Float(f64),
     Bool(bool),
     Str(String),
+    Char(char),
     Range(i64),
+    List(Rc<RefCell<Vec<Type>>>), // shared: copies of a list value see each other's changes
+    Map(Rc<RefCell<Map>>),        // shared like List
+    Function(Rc<Function>),
+    Builtin(&'static str), // a function from `builtins`, by name
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>), // shared like List
     Null,
 }
 
+// Shared containers can hold themselves, as after `xs[0] = xs`, so printing and
+// comparing remember which ones they are already inside instead of recursing forever
+thread_local! {
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+    static COMPARING: RefCell<Vec<(*const (), *const ())>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Run `visit` with `id` marked as active, or return `None` if it already is
+fn guarded<T: PartialEq, R>(active: &'static LocalKey<RefCell<Vec<T>>>, id: T, visit: impl FnOnce() -> R) -> Option<R> {
+    if active.with(|ids| ids.borrow().contains(&id)) {
+        return None;
+    }
+    active.with(|ids| ids.borrow_mut().push(id));
+    let result = visit();
+    active.with(|ids| ids.borrow_mut().pop());
+    Some(result)
+}
+
+/// Compare two shared containers; the same one is always equal to itself, and a pair
+/// met again while it is being compared is taken as equal so the rest decides
+fn shared_eq<T: PartialEq>(a: &Rc<RefCell<T>>, b: &Rc<RefCell<T>>) -> bool {
+    let id = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+    Rc::ptr_eq(a, b) || guarded(&COMPARING, id, || *a.borrow() == *b.borrow()).unwrap_or(true)
+}
+
+impl PartialEq for Type {
+    fn eq(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) => a == b,
+            (Type::Float(a), Type::Float(b)) => a == b,
+            (Type::Bool(a), Type::Bool(b)) => a == b,
+            (Type::Str(a), Type::Str(b)) => a == b,
+            (Type::Char(a), Type::Char(b)) => a == b,
+            (Type::Range(a), Type::Range(b)) => a == b,
+            (Type::List(a), Type::List(b)) => shared_eq(a, b),
+            (Type::Map(a), Type::Map(b)) => shared_eq(a, b),
+            (Type::Function(a), Type::Function(b)) => a == b,
+            (Type::Builtin(a), Type::Builtin(b)) => a == b,
+            (Type::Class(a), Type::Class(b)) => a == b,
+            (Type::Instance(a), Type::Instance(b)) => a == b,
+            (Type::Null, Type::Null) => true,
+            _ => false,
+        }
+    }
+}
+
 impl Type {
     // pub fn is_numeric(&self) -> bool {
     //     matches!(self, Type::Int(_) | Type::Float(_))
@@ -28,137 +122,584 @@ impl Type {
         matches!(self, Type::Null)
     }
 
+    /// A new list holding `items`
+    pub fn list(items: Vec<Type>) -> Type {
+        Type::List(Rc::new(RefCell::new(items)))
+    }
+
+    /// A new map holding `map`
+    pub fn map(map: Map) -> Type {
+        Type::Map(Rc::new(RefCell::new(map)))
+    }
+
+    fn is_zero(&self) -> bool {
+        matches!(self, Type::Int(0) | Type::Float(0.0))
+    }
+
+    /// Name of the value's type, as shown in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Type::Int(_) => "Int",
+            Type::Float(_) => "Float",
+            Type::Bool(_) => "Bool",
+            Type::Str(_) => "Str",
+            Type::Char(_) => "Char",
+            Type::Range(_) => "Range",
+            Type::List(_) => "List",
+            Type::Map(_) => "Map",
+            Type::Function(_) | Type::Builtin(_) => "Function",
+            Type::Class(_) => "Class",
+            Type::Instance(_) => "Instance",
+            Type::Null => "Null",
+        }
+    }
+
+    // unary - operator for Type
+    pub fn negate(self) -> Result<Type, ValueError> {
+        match self {
+            Type::Int(a) => a.checked_neg().map(Type::Int).ok_or_else(ValueError::overflow),
+            Type::Float(a) => Ok(Type::Float(-a)),
+            a => Err(ValueError::invalid_operand("negation", &a)),
+        }
+    }
+
+    // unary + operator for Type, only accepted on numbers
+    pub fn unary_plus(self) -> Result<Type, ValueError> {
+        match self {
+            Type::Int(_) | Type::Float(_) => Ok(self),
+            a => Err(ValueError::invalid_operand("unary plus", &a)),
+        }
+    }
+
+    // ! / not operator for Type
+    pub fn not(self) -> Result<Type, ValueError> {
+        match self {
+            Type::Bool(b) => Ok(Type::Bool(!b)),
+            a => Err(ValueError::invalid_operand("logical not", &a)),
+        }
+    }
+
+    // ~ operator for Type, flips every bit of an Int
+    pub fn bit_not(self) -> Result<Type, ValueError> {
+        match self {
+            Type::Int(a) => Ok(Type::Int(!a)),
+            a => Err(ValueError::invalid_operand("bitwise not", &a)),
+        }
+    }
+
     // Addition operator for Type
-    pub fn add(self, other: Type) -> Type {
+    pub fn add(self, other: Type) -> Result<Type, ValueError> {
         match (self, other) {
-            (Type::Int(a), Type::Int(b)) => Type::Int(a + b),
-            (Type::Float(a), Type::Float(b)) => Type::Float(a + b),
-            (Type::Int(a), Type::Float(b)) => Type::Float(a as f64 + b),
-            (Type::Float(a), Type::Int(b)) => Type::Float(a + b as f64),
+            (Type::Int(a), Type::Int(b)) => a.checked_add(b).map(Type::Int).ok_or_else(ValueError::overflow),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Float(a + b)),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Float(a as f64 + b)),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Float(a + b as f64)),
 
-            (Type::Str(a), Type::Str(b)) => Type::Str(a + &b),
-            _ => panic!("Invalid types for addition"),  // Handle other cases as needed
+            (Type::Str(a), Type::Str(b)) => Ok(Type::Str(a + &b)),
+            (Type::Str(mut a), Type::Char(b)) => {
+                a.push(b);
+                Ok(Type::Str(a))
+            }
+            (Type::Char(a), Type::Str(b)) => Ok(Type::Str(format!("{}{}", a, b))),
+            (a, b) => Err(ValueError::invalid_operands("addition", &a, &b)),
         }
     }
     
     // subtraction operator for Type
-    pub fn subtract(self, other: Type) -> Type {
+    pub fn subtract(self, other: Type) -> Result<Type, ValueError> {
         match (self, other) {
-            (Type::Int(a), Type::Int(b)) => Type::Int(a - b),
-            (Type::Float(a), Type::Float(b)) => Type::Float(a - b),
-            (Type::Int(a), Type::Float(b)) => Type::Float(a as f64 - b),
-            (Type::Float(a), Type::Int(b)) => Type::Float(a - b as f64),
-            _ => panic!("Invalid types for addition"),  // Handle other cases as needed
+            (Type::Int(a), Type::Int(b)) => a.checked_sub(b).map(Type::Int).ok_or_else(ValueError::overflow),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Float(a - b)),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Float(a as f64 - b)),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Float(a - b as f64)),
+            (a, b) => Err(ValueError::invalid_operands("subtraction", &a, &b)),
         }
     }
     // multiplication operator for Type
-    pub fn multiply(self, other: Type) -> Type {
+    pub fn multiply(self, other: Type) -> Result<Type, ValueError> {
         match (self, other) {
-            (Type::Int(a), Type::Int(b)) => Type::Int(a * b),
-            (Type::Float(a), Type::Float(b)) => Type::Float(a * b),
-            (Type::Int(a), Type::Float(b)) => Type::Float(a as f64 * b),
-            (Type::Float(a), Type::Int(b)) => Type::Float(a * b as f64),
-            _ => panic!("Invalid types for addition"),  // Handle other cases as needed
+            (Type::Int(a), Type::Int(b)) => a.checked_mul(b).map(Type::Int).ok_or_else(ValueError::overflow),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Float(a * b)),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Float(a as f64 * b)),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Float(a * b as f64)),
+            (a, b) => Err(ValueError::invalid_operands("multiplication", &a, &b)),
         }
     }
     // division operator for Type
-    pub fn divide(self, other: Type) -> Type {
-        if other.is_null() {
-            panic!("Division by zero");
+    pub fn divide(self, other: Type) -> Result<Type, ValueError> {
+        if other.is_null() || other.is_zero() {
+            return Err(ValueError::division_by_zero());
+        }
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) => a.checked_div(b).map(Type::Int).ok_or_else(ValueError::overflow),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Float(a / b)),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Float(a as f64 / b)),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Float(a / b as f64)),
+            (a, b) => Err(ValueError::invalid_operands("division", &a, &b)),
+        }
+    }
+
+    // // operator for Type, rounds the quotient down towards negative infinity
+    pub fn floor_divide(self, other: Type) -> Result<Type, ValueError> {
+        if other.is_zero() {
+            return Err(ValueError::division_by_zero());
+        }
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) => {
+                let q = a.checked_div(b).ok_or_else(ValueError::overflow)?;
+                // truncation rounded towards zero; step down when the signs differ and there is a remainder
+                if a % b != 0 && (a < 0) != (b < 0) {
+                    Ok(Type::Int(q - 1))
+                } else {
+                    Ok(Type::Int(q))
+                }
+            }
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Float((a / b).floor())),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Float((a as f64 / b).floor())),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Float((a / b as f64).floor())),
+            (a, b) => Err(ValueError::invalid_operands("floor division", &a, &b)),
+        }
+    }
+
+    // % operator for Type; the result takes the sign of the divisor, so `a == (a // b) * b + a % b`
+    pub fn modulo(self, other: Type) -> Result<Type, ValueError> {
+        if other.is_zero() {
+            return Err(ValueError::division_by_zero());
         }
-        if let Type::Int(0) | Type::Float(0.0) = other {
-            panic!("Division by zero");
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) => {
+                let r = a.checked_rem(b).ok_or_else(ValueError::overflow)?;
+                if r != 0 && (r < 0) != (b < 0) {
+                    Ok(Type::Int(r + b))
+                } else {
+                    Ok(Type::Int(r))
+                }
+            }
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Float(float_mod(a, b))),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Float(float_mod(a as f64, b))),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Float(float_mod(a, b as f64))),
+            (a, b) => Err(ValueError::invalid_operands("modulo", &a, &b)),
+        }
+    }
+
+    // ** operator for Type; an Int raised to a negative Int power gives a Float
+    pub fn power(self, other: Type) -> Result<Type, ValueError> {
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) if b < 0 => Ok(Type::Float((a as f64).powf(b as f64))),
+            (Type::Int(a), Type::Int(b)) => u32::try_from(b)
+                .ok()
+                .and_then(|b| a.checked_pow(b))
+                .map(Type::Int)
+                .ok_or_else(ValueError::overflow),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Float(a.powf(b))),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Float((a as f64).powf(b))),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Float(a.powf(b as f64))),
+            (a, b) => Err(ValueError::invalid_operands("exponentiation", &a, &b)),
+        }
+    }
+
+    // & operator for Type
+    pub fn bit_and(self, other: Type) -> Result<Type, ValueError> {
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Int(a & b)),
+            (a, b) => Err(ValueError::invalid_operands("bitwise and", &a, &b)),
+        }
+    }
+
+    // | operator for Type
+    pub fn bit_or(self, other: Type) -> Result<Type, ValueError> {
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Int(a | b)),
+            (a, b) => Err(ValueError::invalid_operands("bitwise or", &a, &b)),
+        }
+    }
+
+    // ^ operator for Type
+    pub fn bit_xor(self, other: Type) -> Result<Type, ValueError> {
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Int(a ^ b)),
+            (a, b) => Err(ValueError::invalid_operands("bitwise xor", &a, &b)),
+        }
+    }
+
+    // << operator for Type; bits shifted past the top are dropped
+    pub fn shift_left(self, other: Type) -> Result<Type, ValueError> {
+        match (self, other) {
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Int(a << shift_amount(b)?)),
+            (a, b) => Err(ValueError::invalid_operands("left shift", &a, &b)),
         }
+    }
+
+    // >> operator for Type; an arithmetic shift, so negative numbers stay negative
+    pub fn shift_right(self, other: Type) -> Result<Type, ValueError> {
         match (self, other) {
-            (Type::Int(a), Type::Int(b)) => Type::Int(a / b),
-            (Type::Float(a), Type::Float(b)) => Type::Float(a / b),
-            (Type::Int(a), Type::Float(b)) => Type::Float(a as f64 / b),
-            (Type::Float(a), Type::Int(b)) => Type::Float(a / b as f64),
-            _ => panic!("Invalid types for addition"),  // Handle other cases as needed
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Int(a >> shift_amount(b)?)),
+            (a, b) => Err(ValueError::invalid_operands("right shift", &a, &b)),
+        }
+    }
+
+    // xs[i] for Type; negative indices count back from the end
+    pub fn index(self, index: Type) -> Result<Type, ValueError> {
+        match (self, index) {
+            (Type::List(items), Type::Int(i)) => {
+                let items = items.borrow();
+                let at = position(i, items.len())?;
+                Ok(items[at].clone())
+            }
+            (Type::Str(s), Type::Int(i)) => {
+                let at = position(i, s.chars().count())?;
+                Ok(Type::Char(s.chars().nth(at).unwrap()))
+            }
+            (Type::Map(map), key) => {
+                let key = Key::from_type(&key)?;
+                map.borrow().get(&key).cloned().ok_or_else(|| {
+                    ValueError::new(codes::KEY_NOT_FOUND, format!("Key not found: {}", key))
+                })
+            }
+            (a, b) => Err(ValueError::invalid_operands("indexing", &a, &b)),
+        }
+    }
+
+    // xs[a:b] for Type, a new List or Str; bounds past either end are clamped like Python
+    pub fn slice(self, start: Option<Type>, end: Option<Type>) -> Result<Type, ValueError> {
+        let bound = |b: Option<Type>, default: i64| match b {
+            None => Ok(default),
+            Some(Type::Int(i)) => Ok(i),
+            Some(other) => Err(ValueError::invalid_operand("slice bound", &other)),
+        };
+        match self {
+            Type::List(items) => {
+                let items = items.borrow();
+                let (from, to) = clamp(bound(start, 0)?, bound(end, i64::MAX)?, items.len());
+                Ok(Type::list(items[from..to].to_vec()))
+            }
+            Type::Str(s) => {
+                let (from, to) = clamp(bound(start, 0)?, bound(end, i64::MAX)?, s.chars().count());
+                Ok(Type::Str(s.chars().skip(from).take(to - from).collect()))
+            }
+            a => Err(ValueError::invalid_operand("slicing", &a)),
+        }
+    }
+
+    // xs[i] = value for Type; only lists can be changed in place
+    pub fn set_index(self, index: Type, value: Type) -> Result<(), ValueError> {
+        match (self, index) {
+            (Type::List(items), Type::Int(i)) => {
+                let mut items = items.borrow_mut();
+                let at = position(i, items.len())?;
+                items[at] = value;
+                Ok(())
+            }
+            (Type::Map(map), key) => {
+                map.borrow_mut().insert(Key::from_type(&key)?, value);
+                Ok(())
+            }
+            (a, b) => Err(ValueError::invalid_operands("index assignment", &a, &b)),
+        }
+    }
+
+    // `item in self` for Type: a key of a Map, an element of a List, or a Char or substring of a Str
+    pub fn contains(self, item: Type) -> Result<Type, ValueError> {
+        match (self, item) {
+            (Type::Map(map), key) => Ok(Type::Bool(map.borrow().contains(&Key::from_type(&key)?))),
+            (Type::List(items), item) => Ok(Type::Bool(items.borrow().contains(&item))),
+            (Type::Str(s), Type::Str(sub)) => Ok(Type::Bool(s.contains(&sub))),
+            (Type::Str(s), Type::Char(c)) => Ok(Type::Bool(s.contains(c))),
+            (a, b) => Err(ValueError::invalid_operands("membership test", &b, &a)),
+        }
+    }
+
+    // `self is class` for Type: whether an instance was made from the class or one inheriting from it
+    pub fn instance_of(self, class: Type) -> Result<Type, ValueError> {
+        match (self, class) {
+            (Type::Instance(instance), Type::Class(class)) => Ok(Type::Bool(instance.borrow().class.is_subclass_of(&class))),
+            (_, Type::Class(_)) => Ok(Type::Bool(false)),
+            (a, b) => Err(ValueError::invalid_operands("class check", &a, &b)),
         }
     }
 
     // equality operator for Type
-    pub fn equal(self, other: Type) -> Type {
-        Type::Bool(self == other)
+    pub fn equal(self, other: Type) -> Result<Type, ValueError> {
+        Ok(Type::Bool(self == other))
     }
 
     // not equal operator for Type
-    pub fn not_equal(self, other: Type) -> Type {
-        Type::Bool(self != other)
+    pub fn not_equal(self, other: Type) -> Result<Type, ValueError> {
+        Ok(Type::Bool(self != other))
     }
 
     // < operator for Type
-    pub fn less_than(self, other: Type) -> Type {
+    pub fn less_than(self, other: Type) -> Result<Type, ValueError> {
         match (self, other) {
             // number comparisons
-            (Type::Int(a), Type::Int(b)) => Type::Bool(a < b),
-            (Type::Float(a), Type::Float(b)) => Type::Bool(a < b),
-            (Type::Int(a), Type::Float(b)) => Type::Bool((a as f64) < b),
-            (Type::Float(a), Type::Int(b)) => Type::Bool(a < (b as f64)),
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Bool(a < b)),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Bool(a < b)),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Bool((a as f64) < b)),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Bool(a < (b as f64))),
 
             // string comparisons
             (Type::Str(a), Type::Str(b)) => {
                 let len_a = a.len() as f64;
                 let len_b = b.len() as f64;
-                Type::Bool(len_a < len_b)
+                Ok(Type::Bool(len_a < len_b))
             }
-            _ => panic!("Invalid types for less than"),  // Handle other cases as needed
+
+            // character comparisons, by code point
+            (Type::Char(a), Type::Char(b)) => Ok(Type::Bool(a < b)),
+            (a, b) => Err(ValueError::invalid_operands("less than", &a, &b)),
         }
     }
 
     // > operator for Type
-    pub fn greater_than(self, other: Type) -> Type {
+    pub fn greater_than(self, other: Type) -> Result<Type, ValueError> {
         match (self, other) {
             // number comparisons
-            (Type::Int(a), Type::Int(b)) => Type::Bool(a > b),
-            (Type::Float(a), Type::Float(b)) => Type::Bool(a > b),
-            (Type::Int(a), Type::Float(b)) => Type::Bool((a as f64) > b),
-            (Type::Float(a), Type::Int(b)) => Type::Bool(a > (b as f64)),
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Bool(a > b)),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Bool(a > b)),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Bool((a as f64) > b)),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Bool(a > (b as f64))),
 
             // String comparisons
             (Type::Str(a), Type::Str(b)) => {
                 let len_a = a.len() as f64;
                 let len_b = b.len() as f64;
-                Type::Bool(len_a > len_b)
+                Ok(Type::Bool(len_a > len_b))
             }
 
-            _ => panic!("Invalid types for greater than"),  // Handle other cases as needed
+            // character comparisons, by code point
+            (Type::Char(a), Type::Char(b)) => Ok(Type::Bool(a > b)),
+            (a, b) => Err(ValueError::invalid_operands("greater than", &a, &b)),
         }
     }
 
     // <= operator for Type
-    pub fn less_than_equal(self, other: Type) -> Type {
+    pub fn less_than_equal(self, other: Type) -> Result<Type, ValueError> {
         match (self, other) {
-            (Type::Int(a), Type::Int(b)) => Type::Bool(a <= b),
-            (Type::Float(a), Type::Float(b)) => Type::Bool(a <= b),
-            (Type::Int(a), Type::Float(b)) => Type::Bool((a as f64) <= b),
-            (Type::Float(a), Type::Int(b)) => Type::Bool(a <=(b as f64)),
-            _ => panic!("Invalid types for less than equal"),  // Handle other cases as needed
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Bool(a <= b)),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Bool(a <= b)),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Bool((a as f64) <= b)),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Bool(a <=(b as f64))),
+            (Type::Char(a), Type::Char(b)) => Ok(Type::Bool(a <= b)),
+            (a, b) => Err(ValueError::invalid_operands("less than equal", &a, &b)),
         }
     }
     // >= operator for Type
-    pub fn greater_than_equal(self, other: Type) -> Type {
+    pub fn greater_than_equal(self, other: Type) -> Result<Type, ValueError> {
         match (self, other) {
-            (Type::Int(a), Type::Int(b)) => Type::Bool(a >= b),
-            (Type::Float(a), Type::Float(b)) => Type::Bool(a >= b),
-            (Type::Int(a), Type::Float(b)) => Type::Bool((a as f64) >= b),
-            (Type::Float(a), Type::Int(b)) => Type::Bool(a >= (b as f64)),
-            _ => panic!("Invalid types for greater than equal"),  // Handle other cases as needed
+            (Type::Int(a), Type::Int(b)) => Ok(Type::Bool(a >= b)),
+            (Type::Float(a), Type::Float(b)) => Ok(Type::Bool(a >= b)),
+            (Type::Int(a), Type::Float(b)) => Ok(Type::Bool((a as f64) >= b)),
+            (Type::Float(a), Type::Int(b)) => Ok(Type::Bool(a >= (b as f64))),
+            (Type::Char(a), Type::Char(b)) => Ok(Type::Bool(a >= b)),
+            (a, b) => Err(ValueError::invalid_operands("greater than equal", &a, &b)),
         }
     }
 }
 
+/// Where index `i` points in a sequence of `len` items, counting negative indices from the end
+fn position(i: i64, len: usize) -> Result<usize, ValueError> {
+    let at = if i < 0 { i + len as i64 } else { i };
+    if (0..len as i64).contains(&at) {
+        Ok(at as usize)
+    } else {
+        Err(ValueError::new(
+            codes::INDEX_OUT_OF_BOUNDS,
+            format!("Index {} is out of bounds for length {}", i, len),
+        ))
+    }
+}
+
+/// Turn slice bounds into a valid `from..to` range of a sequence of `len` items
+fn clamp(start: i64, end: i64, len: usize) -> (usize, usize) {
+    let len = len as i64;
+    let fix = |b: i64| if b < 0 { (b + len).max(0) } else { b.min(len) };
+    let (from, to) = (fix(start), fix(end));
+    (from as usize, to.max(from) as usize)
+}
+
+/// Float remainder with the sign of the divisor, matching Int `%`
+fn float_mod(a: f64, b: f64) -> f64 {
+    let r = a % b;
+    if r != 0.0 && (r < 0.0) != (b < 0.0) { r + b } else { r }
+}
+
+/// Shift distances must fit in the 64 bits of an Int
+fn shift_amount(b: i64) -> Result<u32, ValueError> {
+    if (0..64).contains(&b) {
+        Ok(b as u32)
+    } else {
+        Err(ValueError::new(
+            codes::SHIFT_OUT_OF_RANGE,
+            format!("Shift amount {} is out of range, expected 0 to 63", b),
+        ))
+    }
+}
+
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Type::Str(s) => write!(f, "{}", s),
+            Type::Char(c) => write!(f, "{}", c),
             Type::Int(i) => write!(f, "{}", i),
             Type::Bool(b) => write!(f, "{}", b),
             Type::Float(n) => write!(f, "{}", n),
             Type::Range(r) => write!(f, "{}", r),
+            Type::List(items) => {
+                let id = Rc::as_ptr(items) as *const ();
+                guarded(&PRINTING, id, || {
+                    write!(f, "[")?;
+                    for (i, item) in items.borrow().iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write_item(f, item)?;
+                    }
+                    write!(f, "]")
+                })
+                .unwrap_or_else(|| write!(f, "[...]"))
+            }
+            Type::Map(map) => {
+                let id = Rc::as_ptr(map) as *const ();
+                guarded(&PRINTING, id, || {
+                    write!(f, "{{")?;
+                    for (i, (key, value)) in map.borrow().iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write_item(f, &key.to_type())?;
+                        write!(f, ": ")?;
+                        write_item(f, value)?;
+                    }
+                    write!(f, "}}")
+                })
+                .unwrap_or_else(|| write!(f, "{{...}}"))
+            }
+            Type::Function(function) => write!(f, "<fn {}>", function.name),
+            Type::Builtin(name) => write!(f, "<fn {}>", name),
+            Type::Class(class) => write!(f, "<class {}>", class.name),
+            Type::Instance(shared) => {
+                let instance = shared.borrow();
+                let names = instance.class.field_names();
+                if names.is_empty() {
+                    return write!(f, "{} {{}}", instance.class.name);
+                }
+                let id = Rc::as_ptr(shared) as *const ();
+                guarded(&PRINTING, id, || {
+                    write!(f, "{} {{ ", instance.class.name)?;
+                    for (i, name) in names.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}: ", name)?;
+                        write_item(f, &instance.fields[*name])?;
+                    }
+                    write!(f, " }}")
+                })
+                .unwrap_or_else(|| write!(f, "{} {{ ... }}", instance.class.name))
+            }
             Type::Null => write!(f, "null"),
         }
     }
 }
 
+
+/// Write a value inside a List or Map, quoting text so `["1", 1]` does not print as `[1, 1]`
+fn write_item(f: &mut fmt::Formatter, item: &Type) -> fmt::Result {
+    match item {
+        Type::Str(s) => write!(f, "{:?}", s),
+        Type::Char(c) => write!(f, "{:?}", c),
+        other => write!(f, "{}", other),
+    }
+}
+
+/// A value that can be used as a Map key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Int(i64),
+    Str(String),
+    Bool(bool),
+    Char(char),
+}
+
+impl Key {
+    /// Only values that cannot change and compare exactly can be keys; Floats, Lists and Maps cannot
+    pub fn from_type(value: &Type) -> Result<Key, ValueError> {
+        match value {
+            Type::Int(i) => Ok(Key::Int(*i)),
+            Type::Str(s) => Ok(Key::Str(s.clone())),
+            Type::Bool(b) => Ok(Key::Bool(*b)),
+            Type::Char(c) => Ok(Key::Char(*c)),
+            other => Err(ValueError::new(
+                codes::UNHASHABLE_KEY,
+                format!("Unhashable map key: {} cannot be used as a key", other.type_name()),
+            )),
+        }
+    }
+
+    pub fn to_type(&self) -> Type {
+        match self {
+            Key::Int(i) => Type::Int(*i),
+            Key::Str(s) => Type::Str(s.clone()),
+            Key::Bool(b) => Type::Bool(*b),
+            Key::Char(c) => Type::Char(*c),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_item(f, &self.to_type())
+    }
+}
+
+/// Key/value pairs that iterate in the order their keys were first inserted
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Key, Type)>,
+    slots: HashMap<Key, usize>, // where each key sits in `entries`
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Map::default()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Type> {
+        self.slots.get(key).map(|&slot| &self.entries[slot].1)
+    }
+
+    /// Add or replace the value for `key`; a replaced key keeps its original position
+    pub fn insert(&mut self, key: Key, value: Type) {
+        match self.slots.get(&key) {
+            Some(&slot) => self.entries[slot].1 = value,
+            None => {
+                self.slots.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.slots.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Key, Type)> {
+        self.entries.iter()
+    }
+
+    /// The keys as values, in insertion order
+    pub fn keys(&self) -> Vec<Type> {
+        self.entries.iter().map(|(key, _)| key.to_type()).collect()
+    }
+}
+
+/// Maps are equal when they hold the same pairs, whatever order they were inserted in
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        self.len() == other.len() && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}